@@ -0,0 +1,189 @@
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{read_to_string, write},
+    num::NonZeroU32,
+    path::Path,
+};
+
+use crate::secret::{from_hex, pbkdf2_hash, pbkdf2_matches, random_bytes, to_hex, Key};
+
+// An answers line is `func input answer`. A hashed answer is stored as
+// `hash:<iterations>:<salt>:<PBKDF2-HMAC-SHA256 of the answer>[:<answer sealed with the local
+// key>]`, so the file can be published while it still catches wrong answers. The iterations
+// make every guess slow, but short numeric answers can still be found by trying them all.
+pub enum Expected {
+    Plain(String),
+    Hashed {
+        iterations: NonZeroU32,
+        salt: Vec<u8>,
+        hash: Vec<u8>,
+        sealed: Option<Vec<u8>>,
+    },
+}
+
+// checking every answer of a run takes about a second with this many
+const HASH_ITERATIONS: NonZeroU32 = NonZeroU32::new(100_000).unwrap();
+
+impl Expected {
+    fn parse(answer: &str) -> Result<Expected, Box<dyn Error>> {
+        let Some(hashed) = answer.strip_prefix("hash:") else {
+            return Ok(Expected::Plain(answer.to_string()));
+        };
+        let mut parts = hashed.split(':');
+        let iterations = parts
+            .next()
+            .and_then(|i| i.parse().ok())
+            .ok_or("Expected a number of iterations")?;
+        let salt = from_hex(parts.next().ok_or("Expected salt")?)?;
+        let hash = from_hex(parts.next().ok_or("Expected hash")?)?;
+        let sealed = parts.next().map(from_hex).transpose()?;
+        if parts.next().is_some() {
+            Err("Unexpected data after sealed answer")?;
+        }
+        Ok(Expected::Hashed {
+            iterations,
+            salt,
+            hash,
+            sealed,
+        })
+    }
+
+    fn hashed(name: &str, answer: &str, key: Option<&Key>) -> Expected {
+        let salt = random_bytes::<16>().to_vec();
+        Expected::Hashed {
+            iterations: HASH_ITERATIONS,
+            hash: pbkdf2_hash(HASH_ITERATIONS, &salt, answer.as_bytes()),
+            salt,
+            sealed: key.map(|key| key.seal(name.as_bytes(), answer.as_bytes())),
+        }
+    }
+
+    fn to_entry(&self) -> String {
+        match self {
+            Expected::Plain(answer) => answer.clone(),
+            Expected::Hashed {
+                iterations,
+                salt,
+                hash,
+                sealed,
+            } => {
+                let mut entry = format!("hash:{iterations}:{}:{}", to_hex(salt), to_hex(hash));
+                if let Some(sealed) = sealed {
+                    entry += &format!(":{}", to_hex(sealed));
+                }
+                entry
+            }
+        }
+    }
+
+    pub fn matches(&self, answer: &str) -> bool {
+        match self {
+            Expected::Plain(expected) => expected == answer,
+            Expected::Hashed {
+                iterations,
+                salt,
+                hash,
+                ..
+            } => pbkdf2_matches(*iterations, salt, answer.as_bytes(), hash),
+        }
+    }
+
+    // the answer itself if it is stored in plain text
+    pub fn plain(&self) -> Option<&str> {
+        match self {
+            Expected::Plain(answer) => Some(answer),
+            Expected::Hashed { .. } => None,
+        }
+    }
+
+    fn reveal(&self, name: &str, key: &Key) -> Result<String, Box<dyn Error>> {
+        match self {
+            Expected::Plain(answer) => Ok(answer.clone()),
+            Expected::Hashed { sealed: None, .. } => Err("Stored without a key".into()),
+            Expected::Hashed {
+                sealed: Some(sealed),
+                ..
+            } => {
+                let answer = String::from_utf8(key.open(name.as_bytes(), sealed)?)?;
+                if !self.matches(&answer) {
+                    Err("Sealed answer does not match its hash")?;
+                }
+                Ok(answer)
+            }
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<(String, Expected), Box<dyn Error>> {
+    let (func, input, answer) = line
+        .splitn(3, ' ')
+        .tuples()
+        .next()
+        .ok_or_else(|| format!("Could not read answer line {line}"))?;
+    Ok((format!("{func} {input}"), Expected::parse(answer)?))
+}
+
+fn read_lines(data_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let path = data_dir.join("answers");
+    let content =
+        read_to_string(&path).map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    Ok(content.lines().map(str::to_string).collect())
+}
+
+pub struct Answers(HashMap<String, Expected>);
+
+impl Answers {
+    pub fn load(data_dir: &Path) -> Result<Answers, Box<dyn Error>> {
+        let answers = read_lines(data_dir)?
+            .iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| parse_line(line))
+            .try_collect()?;
+        Ok(Answers(answers))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expected> {
+        self.0.get(name)
+    }
+}
+
+// hash-answers replaces every plain answer by its hash, sealed with the key when there is one
+pub fn hash_answers(data_dir: &Path) -> Result<(), Box<dyn Error>> {
+    let key = Key::load(data_dir)?;
+    if key.is_none() {
+        eprintln!("No key found, the hashed answers cannot be revealed later");
+    }
+    let mut count = 0;
+    let mut lines = read_lines(data_dir)?;
+    for line in lines.iter_mut().filter(|line| !line.is_empty()) {
+        let (name, expected) = parse_line(line)?;
+        if let Some(answer) = expected.plain() {
+            *line = format!(
+                "{name} {}",
+                Expected::hashed(&name, answer, key.as_ref()).to_entry()
+            );
+            count += 1;
+        }
+    }
+    write(data_dir.join("answers"), lines.join("\n") + "\n")?;
+    println!("Hashed {count} answers");
+    Ok(())
+}
+
+// reveal [<filter>] prints the answers in plain text, which needs the key
+pub fn reveal(data_dir: &Path, filter: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let key = Key::load_required(data_dir)?;
+    for line in read_lines(data_dir)?.iter().filter(|line| !line.is_empty()) {
+        let (name, expected) = parse_line(line)?;
+        if filter.is_some_and(|filter| !name.starts_with(filter)) {
+            continue;
+        }
+        match expected.reveal(&name, &key) {
+            Ok(answer) => println!("{name} {answer}"),
+            Err(e) => eprintln!("{name} could not be revealed: {e}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+};
+
+// Shared between a long running solver and whoever runs it. The solver reports how far it
+// got and checks regularly whether it should stop, the runner shows the progress and cancels
+// it on a timeout. All of it is a few relaxed atomics, so it is cheap to call in hot loops.
+pub struct Context {
+    cancelled: AtomicBool,
+    // in millionths, u32::MAX while unknown
+    progress: AtomicU32,
+    states: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::new()
+    }
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            cancelled: AtomicBool::new(false),
+            progress: AtomicU32::new(u32::MAX),
+            states: AtomicU64::new(0),
+        }
+    }
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+    pub fn set_progress(&self, fraction: f64) {
+        let millionths = (fraction.clamp(0.0, 1.0) * 1e6) as u32;
+        self.progress.store(millionths, Ordering::Relaxed);
+    }
+    pub fn progress(&self) -> Option<f64> {
+        match self.progress.load(Ordering::Relaxed) {
+            u32::MAX => None,
+            millionths => Some(millionths as f64 / 1e6),
+        }
+    }
+    pub fn add_states(&self, states: u64) {
+        self.states.fetch_add(states, Ordering::Relaxed);
+    }
+    pub fn states(&self) -> u64 {
+        self.states.load(Ordering::Relaxed)
+    }
+}
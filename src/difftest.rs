@@ -0,0 +1,130 @@
+use std::{
+    fmt::Display,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::random::Rng;
+
+enum Outcome {
+    Answer(String),
+    Failed(String),
+}
+
+impl Outcome {
+    fn of<R: Display, E: Display>(func: impl Fn(&[u8]) -> Result<R, E>, input: &[u8]) -> Outcome {
+        // a panic is just another way to reject the input
+        match catch_unwind(AssertUnwindSafe(|| func(input))) {
+            Ok(Ok(r)) => Outcome::Answer(r.to_string()),
+            Ok(Err(e)) => Outcome::Failed(e.to_string()),
+            Err(_) => Outcome::Failed("panicked".into()),
+        }
+    }
+    fn agrees_with(&self, other: &Outcome) -> bool {
+        match (self, other) {
+            (Outcome::Answer(l), Outcome::Answer(r)) => l == r,
+            (Outcome::Failed(_), Outcome::Failed(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Answer(a) => write!(f, "{a}"),
+            Outcome::Failed(e) => write!(f, "error: {e}"),
+        }
+    }
+}
+
+// Removes ever smaller chunks of pieces for as long as the input still disagrees
+fn minimize_pieces<'a>(
+    mut pieces: Vec<&'a [u8]>,
+    disagrees: &impl Fn(&[u8]) -> bool,
+) -> Vec<&'a [u8]> {
+    let mut chunk = pieces.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        while start < pieces.len() {
+            let end = (start + chunk).min(pieces.len());
+            let candidate = [&pieces[..start], &pieces[end..]].concat().concat();
+            if disagrees(&candidate) {
+                pieces.drain(start..end);
+            } else {
+                start += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+    pieces
+}
+
+pub fn minimize(input: &[u8], disagrees: impl Fn(&[u8]) -> bool) -> Vec<u8> {
+    let mut pieces = minimize_pieces(input.split_inclusive(|b| *b == b'\n').collect(), &disagrees);
+    if pieces.len() == 1 {
+        // inputs like q06 and q17 are a single line
+        pieces = minimize_pieces(pieces[0].chunks(1).collect(), &disagrees);
+    }
+    pieces.concat()
+}
+
+// mostly small inputs, which minimize quickly, but some bugs only show in larger ones
+const SIZES: [usize; 12] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 50, 200];
+
+// Runs a solver and its reference on generated inputs of growing size. Returns false and
+// prints a minimized input for the first input where they do not agree. Inputs that both
+// reject test nothing, so they are counted apart and it fails when all inputs are rejected.
+pub fn compare<R1, E1, R2, E2>(
+    name: &str,
+    generate: fn(&mut Rng, usize, &mut Vec<u8>) -> std::io::Result<()>,
+    solver: impl Fn(&[u8]) -> Result<R1, E1>,
+    reference: impl Fn(&[u8]) -> Result<R2, E2>,
+    runs: usize,
+    seed: u64,
+) -> std::io::Result<bool>
+where
+    R1: Display,
+    E1: Display,
+    R2: Display,
+    E2: Display,
+{
+    let mut rejected = 0;
+    for run in 0..runs {
+        let size = SIZES[run % SIZES.len()];
+        let mut input = Vec::new();
+        generate(&mut Rng::new(seed + run as u64), size, &mut input)?;
+        let disagrees = |input: &[u8]| {
+            !Outcome::of(&solver, input).agrees_with(&Outcome::of(&reference, input))
+        };
+        let (ours, theirs) = (
+            Outcome::of(&solver, &input),
+            Outcome::of(&reference, &input),
+        );
+        if let (Outcome::Failed(_), Outcome::Failed(_)) = (&ours, &theirs) {
+            rejected += 1;
+        } else if !ours.agrees_with(&theirs) {
+            let input = minimize(&input, disagrees);
+            println!(
+                "{name} disagrees with the reference for seed {} and size {size}, minimized input:",
+                seed + run as u64
+            );
+            println!("{}", String::from_utf8_lossy(&input));
+            println!("solver: {}", Outcome::of(&solver, &input));
+            println!("reference: {}", Outcome::of(&reference, &input));
+            return Ok(false);
+        }
+    }
+    if runs > 0 && rejected == runs {
+        println!("{name} and the reference reject all {runs} generated inputs");
+        return Ok(false);
+    }
+    if rejected > 0 {
+        println!(
+            "{name} agrees with the reference on {} inputs, both reject {rejected} more",
+            runs - rejected
+        );
+    } else {
+        println!("{name} agrees with the reference on {runs} inputs");
+    }
+    Ok(true)
+}
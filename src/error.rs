@@ -0,0 +1,208 @@
+use std::{
+    char::ParseCharError,
+    convert::Infallible,
+    error::Error,
+    fmt::Display,
+    io,
+    num::{ParseFloatError, ParseIntError, TryFromIntError},
+    str::{ParseBoolError, Utf8Error},
+    time::Duration,
+};
+
+use crate::{context::Cancelled, parsing::combinators::ParseError};
+
+// The error of every solver, so that callers can tell bad input from a puzzle without a
+// solution. Parse errors know where they happened and are shown with the failing line and a
+// caret under the failing column.
+#[derive(Debug)]
+pub enum AocError {
+    Io(io::Error),
+    Parse {
+        line: Option<usize>,
+        column: Option<usize>,
+        snippet: String,
+        message: String,
+    },
+    InvalidInput(String),
+    NoSolution(String),
+    Overflow(String),
+    Cancelled,
+    TimedOut(Duration),
+}
+
+impl AocError {
+    pub fn no_solution(message: impl Into<String>) -> AocError {
+        AocError::NoSolution(message.into())
+    }
+
+    pub fn overflow(message: impl Into<String>) -> AocError {
+        AocError::Overflow(message.into())
+    }
+
+    // Places an error that happened while parsing a line of the input on that line
+    pub fn at_line(self, line: usize, text: &str) -> AocError {
+        match self {
+            AocError::Parse {
+                line: None,
+                column,
+                snippet,
+                message,
+            } => AocError::Parse {
+                line: Some(line),
+                column,
+                snippet: if snippet.is_empty() {
+                    text.to_string()
+                } else {
+                    snippet
+                },
+                message,
+            },
+            AocError::InvalidInput(message) => AocError::Parse {
+                line: Some(line),
+                column: None,
+                snippet: text.to_string(),
+                message,
+            },
+            error => error,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AocError::Io(_) => "io",
+            AocError::Parse { .. } => "parse",
+            AocError::InvalidInput(_) => "invalid_input",
+            AocError::NoSolution(_) => "no_solution",
+            AocError::Overflow(_) => "overflow",
+            AocError::Cancelled => "cancelled",
+            AocError::TimedOut(_) => "timed_out",
+        }
+    }
+}
+
+impl Display for AocError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AocError::Io(e) => write!(f, "I/O error: {e}"),
+            AocError::Parse {
+                line,
+                column,
+                snippet,
+                message,
+            } => {
+                write!(f, "Parse error")?;
+                match (line, column) {
+                    (Some(line), Some(column)) => write!(f, " on line {line}, column {column}")?,
+                    (Some(line), None) => write!(f, " on line {line}")?,
+                    (None, Some(column)) => write!(f, " at column {column}")?,
+                    (None, None) => {}
+                }
+                write!(f, ": {message}")?;
+                if !snippet.is_empty() {
+                    write!(f, "\n  {snippet}")?;
+                    if let Some(column) = column {
+                        write!(f, "\n  {}^", " ".repeat(column - 1))?;
+                    }
+                }
+                Ok(())
+            }
+            AocError::InvalidInput(message) => write!(f, "{message}"),
+            AocError::NoSolution(message) => write!(f, "No solution: {message}"),
+            AocError::Overflow(message) => write!(f, "Overflow: {message}"),
+            AocError::Cancelled => write!(f, "Cancelled"),
+            AocError::TimedOut(elapsed) => {
+                write!(f, "Timed out after {:.1}s", elapsed.as_secs_f64())
+            }
+        }
+    }
+}
+
+impl Error for AocError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AocError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AocError {
+    fn from(e: io::Error) -> Self {
+        AocError::Io(e)
+    }
+}
+
+impl From<&str> for AocError {
+    fn from(message: &str) -> Self {
+        AocError::InvalidInput(message.to_string())
+    }
+}
+
+impl From<String> for AocError {
+    fn from(message: String) -> Self {
+        AocError::InvalidInput(message)
+    }
+}
+
+impl From<ParseError> for AocError {
+    fn from(e: ParseError) -> Self {
+        AocError::Parse {
+            line: None,
+            column: Some(e.column),
+            message: e.describe(),
+            snippet: e.input,
+        }
+    }
+}
+
+// a value that does not fit in the type the answer needs
+impl From<TryFromIntError> for AocError {
+    fn from(e: TryFromIntError) -> Self {
+        AocError::Overflow(e.to_string())
+    }
+}
+
+// String and other types that always parse
+impl From<Infallible> for AocError {
+    fn from(e: Infallible) -> Self {
+        match e {}
+    }
+}
+
+impl From<Cancelled> for AocError {
+    fn from(_: Cancelled) -> Self {
+        AocError::Cancelled
+    }
+}
+
+// errors from the standard library that mean the input was not what we expected
+macro_rules! invalid_input_from {
+    ($($t:ty),*) => {
+        $(impl From<$t> for AocError {
+            fn from(e: $t) -> Self {
+                AocError::InvalidInput(e.to_string())
+            }
+        })*
+    };
+}
+
+invalid_input_from!(
+    ParseIntError,
+    ParseFloatError,
+    ParseBoolError,
+    ParseCharError,
+    Utf8Error
+);
+
+// for errors of code that is not specific to the puzzles, like decrypting inputs
+impl From<Box<dyn Error>> for AocError {
+    fn from(e: Box<dyn Error>) -> Self {
+        match e.downcast::<AocError>() {
+            Ok(e) => *e,
+            Err(e) => match e.downcast::<io::Error>() {
+                Ok(e) => AocError::Io(*e),
+                Err(e) => AocError::InvalidInput(e.to_string()),
+            },
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::mpsc::channel,
+    thread,
+    time::Duration,
+};
+
+use crate::{difftest::minimize, error::AocError, random::Rng};
+
+// parses an input and writes it back, see ToBufWrite
+pub type Reformat = fn(&[u8]) -> Result<Vec<u8>, AocError>;
+
+// a parser that takes longer than this on a few kilobytes is stuck
+const TIME_LIMIT: Duration = Duration::from_secs(2);
+
+// bytes that mean something to at least one of the parsers
+const INTERESTING: &[u8] =
+    b"\n -+*,:;=[]0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ#.<>\xff";
+
+const NUMBERS: &[&str] = &[
+    "0",
+    "-1",
+    "255",
+    "256",
+    "32767",
+    "32768",
+    "-32769",
+    "2147483648",
+    "9223372036854775808",
+    "99999999999999999999",
+];
+
+fn range(rng: &mut Rng, len: usize) -> (usize, usize) {
+    let start = rng.below(len + 1);
+    (start, (start + 1 + rng.below(16)).min(len))
+}
+
+fn mutate_once(rng: &mut Rng, input: &mut Vec<u8>) {
+    let len = input.len();
+    match rng.below(8) {
+        0 if len > 0 => input[rng.below(len)] = *rng.choose(INTERESTING),
+        1 => input.insert(rng.below(len + 1), *rng.choose(INTERESTING)),
+        2 => {
+            let (start, end) = range(rng, len);
+            input.drain(start..end);
+        }
+        3 => {
+            let (start, end) = range(rng, len);
+            let copy = input[start..end].to_vec();
+            let at = rng.below(len + 1);
+            input.splice(at..at, copy);
+        }
+        4 => input.truncate(rng.below(len + 1)),
+        5 => {
+            // swaps two lines, or drops one when they are the same
+            let mut lines: Vec<Vec<u8>> = input.split(|b| *b == b'\n').map(Vec::from).collect();
+            let (l, r) = (rng.below(lines.len()), rng.below(lines.len()));
+            if l == r {
+                lines.remove(l);
+            } else {
+                lines.swap(l, r);
+            }
+            *input = lines.join(&b'\n');
+        }
+        _ => {
+            // replaces the digits around a random position with an edge case number
+            let at = rng.below(len + 1);
+            let start = at
+                - input[..at]
+                    .iter()
+                    .rev()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+            let end = at
+                + input[at..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit())
+                    .count();
+            input.splice(start..end, rng.choose(NUMBERS).bytes());
+        }
+    }
+}
+
+fn mutate(rng: &mut Rng, original: &[u8]) -> Vec<u8> {
+    let mut input = original.to_vec();
+    for _ in 0..=rng.below(4) {
+        mutate_once(rng, &mut input);
+    }
+    input
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Finding {
+    Panic,
+    // what was written back does not read as the same value
+    NoRoundTrip,
+}
+
+fn check(reformat: Reformat, input: &[u8]) -> Option<Finding> {
+    let reformat = |input: &[u8]| catch_unwind(AssertUnwindSafe(|| reformat(input)));
+    match reformat(input) {
+        Err(_) => Some(Finding::Panic),
+        Ok(Err(_)) => None,
+        Ok(Ok(written)) => match reformat(&written) {
+            Err(_) => Some(Finding::Panic),
+            Ok(Ok(rewritten)) if rewritten == written => None,
+            Ok(_) => Some(Finding::NoRoundTrip),
+        },
+    }
+}
+
+// Runs a day's parser on mutated versions of the corpus inputs. Every input it accepts has to
+// survive being written back and read again. Returns false and prints the input for the first
+// one that makes the parser panic, fail that round trip or take longer than TIME_LIMIT.
+pub fn fuzz(name: &str, reformat: Reformat, corpus: &[Vec<u8>], runs: usize, seed: u64) -> bool {
+    // the parser runs on its own thread, so a stuck parser can be left behind
+    let (input_sender, input_receiver) = channel::<Vec<u8>>();
+    let (result_sender, result_receiver) = channel();
+    thread::spawn(move || {
+        for input in input_receiver {
+            if result_sender.send(check(reformat, &input)).is_err() {
+                break;
+            }
+        }
+    });
+    let mut rng = Rng::new(seed);
+    for run in 0..runs {
+        let original = rng.choose(corpus);
+        let input = mutate(&mut rng, original);
+        // the thread only stops early when a panic escaped catch_unwind
+        if input_sender.send(input.clone()).is_err() {
+            return false;
+        }
+        match result_receiver.recv_timeout(TIME_LIMIT) {
+            Ok(None) => {}
+            Ok(Some(finding)) => {
+                let input = minimize(&input, |input| check(reformat, input) == Some(finding));
+                match finding {
+                    Finding::Panic => println!("{name} panics on run {run}, minimized input:"),
+                    Finding::NoRoundTrip => println!(
+                        "{name} reads a different value back on run {run}, minimized input:"
+                    ),
+                }
+                println!("{}", String::from_utf8_lossy(&input));
+                return false;
+            }
+            Err(_) => {
+                println!(
+                    "{name} did not finish within {}s on run {run}, input:",
+                    TIME_LIMIT.as_secs()
+                );
+                println!("{}", String::from_utf8_lossy(&input));
+                return false;
+            }
+        }
+    }
+    println!("{name} handled {runs} mutated inputs");
+    true
+}
@@ -1,61 +1,601 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
+    hash::Hash,
+    iter::{repeat_n, successors},
     ops::{Index, IndexMut},
 };
 
-use crate::pos::Pos;
+use crate::{error::AocError, pos::Pos};
+
+// A coordinate type for grids, positions are converted to indices with checks so negative or
+// too large coordinates are outside the grid instead of wrapping around. Only setting grids
+// up goes through i128, the accesses use the type's own checked arithmetic.
+pub trait Coordinate: Copy + Display + Hash + Ord {
+    fn to_i128(self) -> i128;
+    fn from_i128(value: i128) -> Option<Self>;
+    fn to_index(self) -> Option<usize>;
+    fn from_index(index: usize) -> Option<Self>;
+    fn offset(self, delta: i8) -> Option<Self>;
+    fn minus(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! coordinate {
+    ($($t:ty: $offset:ident),*) => {
+        $(impl Coordinate for $t {
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+            fn from_i128(value: i128) -> Option<Self> {
+                Self::try_from(value).ok()
+            }
+            fn to_index(self) -> Option<usize> {
+                usize::try_from(self).ok()
+            }
+            fn from_index(index: usize) -> Option<Self> {
+                Self::try_from(index).ok()
+            }
+            fn offset(self, delta: i8) -> Option<Self> {
+                self.$offset(delta.into())
+            }
+            fn minus(self, other: Self) -> Option<Self> {
+                self.checked_sub(other)
+            }
+        })*
+    };
+}
+
+coordinate!(i16: checked_add, i32: checked_add, i64: checked_add, usize: checked_add_signed);
+
+// the steps to the neighbours sharing a side, and to the ones only sharing a corner
+pub const SIDES: [Pos<i8>; 4] = [
+    Pos { x: -1, y: 0 },
+    Pos { x: 1, y: 0 },
+    Pos { x: 0, y: -1 },
+    Pos { x: 0, y: 1 },
+];
+pub const DIAGONALS: [Pos<i8>; 4] = [
+    Pos { x: -1, y: -1 },
+    Pos { x: -1, y: 1 },
+    Pos { x: 1, y: -1 },
+    Pos { x: 1, y: 1 },
+];
+
+// What all grid types have in common, so an algorithm can run on any of them
+pub trait GridAccess<T, PT>:
+    for<'a> Index<&'a Pos<PT>, Output = T> + for<'a> IndexMut<&'a Pos<PT>>
+{
+    fn get(&self, pos: &Pos<PT>) -> Option<&T>;
+    fn get_mut(&mut self, pos: &Pos<PT>) -> Option<&mut T>;
+    // the corners of the area that holds values, both included. None if there are none.
+    fn bounds(&self) -> Option<(Pos<PT>, Pos<PT>)>;
+}
+
+// the smallest rectangle covering bounds and pos
+fn cover<PT: Coordinate>(bounds: Option<(Pos<PT>, Pos<PT>)>, pos: Pos<PT>) -> (Pos<PT>, Pos<PT>) {
+    let (min, max) = bounds.unwrap_or((pos, pos));
+    (
+        Pos {
+            x: min.x.min(pos.x),
+            y: min.y.min(pos.y),
+        },
+        Pos {
+            x: max.x.max(pos.x),
+            y: max.y.max(pos.y),
+        },
+    )
+}
 
 #[derive(Clone)]
 pub struct Grid<T, PT> {
     values: Vec<T>,
     size: Pos<PT>,
+    width: usize,
+    height: usize,
 }
 
-impl<T, PT> Grid<T, PT>
-where
-    T: Clone,
-    Pos<PT>: Copy,
-{
-    pub fn from_values(values: Vec<T>, size: Pos<PT>) -> Self {
-        Grid { values, size }
+impl<T, PT: Coordinate> Grid<T, PT> {
+    pub fn from_values(values: Vec<T>, size: Pos<PT>) -> Result<Self, AocError> {
+        match (size.x.to_index(), size.y.to_index()) {
+            (Some(width), Some(height)) if width.checked_mul(height) == Some(values.len()) => {
+                Ok(Grid {
+                    values,
+                    size,
+                    width,
+                    height,
+                })
+            }
+            _ => Err(format!(
+                "{} values do not fill a {}x{} grid",
+                values.len(),
+                size.x,
+                size.y
+            ))?,
+        }
+    }
+    pub fn from_value(value: T, size: Pos<PT>) -> Result<Self, AocError>
+    where
+        T: Clone,
+    {
+        let cells = size
+            .x
+            .to_index()
+            .zip(size.y.to_index())
+            .and_then(|(width, height)| width.checked_mul(height))
+            .ok_or_else(|| format!("Cannot make a {}x{} grid", size.x, size.y))?;
+        Grid::from_values(vec![value; cells], size)
     }
     pub fn size(&self) -> Pos<PT> {
         self.size
     }
+    fn index_of(&self, pos: &Pos<PT>) -> Option<usize> {
+        let (x, y) = (pos.x.to_index()?, pos.y.to_index()?);
+        (x < self.width && y < self.height).then(|| x + y * self.width)
+    }
+    pub fn in_bounds(&self, pos: &Pos<PT>) -> bool {
+        self.index_of(pos).is_some()
+    }
+    // all positions, row by row
+    pub fn positions(&self) -> impl Iterator<Item = Pos<PT>> {
+        let width = self.width;
+        (0..self.values.len()).map(move |index| Pos {
+            // cannot fail, the positions are smaller than the size
+            x: PT::from_index(index % width).unwrap(),
+            y: PT::from_index(index / width).unwrap(),
+        })
+    }
+    // the position one step away, if it is in the grid
+    pub fn step(&self, pos: &Pos<PT>, step: Pos<i8>) -> Option<Pos<PT>> {
+        let next = Pos {
+            x: pos.x.offset(step.x)?,
+            y: pos.y.offset(step.y)?,
+        };
+        self.in_bounds(&next).then_some(next)
+    }
+    // the values from pos to the edge in a direction, not including pos itself
+    pub fn line(&self, pos: &Pos<PT>, step: Pos<i8>) -> impl Iterator<Item = &T> {
+        successors(self.step(pos, step), move |p| self.step(p, step)).map(|p| &self[&p])
+    }
+    pub fn view(&self) -> View<'_, T, PT> {
+        View {
+            grid: self,
+            min: Pos { x: 0, y: 0 },
+            size: Pos {
+                x: self.width,
+                y: self.height,
+            },
+            flip_x: false,
+            flip_y: false,
+            transposed: false,
+        }
+    }
+    // The neighbours in the grid, one step away. Use SIDES for 4 neighbours, and SIDES with
+    // DIAGONALS for 8.
+    pub fn neighbours<'a, S>(
+        &'a self,
+        pos: &Pos<PT>,
+        steps: S,
+    ) -> impl Iterator<Item = Pos<PT>> + 'a
+    where
+        S: IntoIterator<Item = Pos<i8>>,
+        S::IntoIter: 'a,
+    {
+        let pos = *pos;
+        steps
+            .into_iter()
+            .filter_map(move |step| self.step(&pos, step))
+    }
+}
+
+// A borrowed rectangle of a grid, which can be transposed, flipped and rotated without copying
+// anything. Positions in a view count from its own top left corner. Columns are the rows of
+// the transposed view.
+pub struct View<'a, T, PT> {
+    grid: &'a Grid<T, PT>,
+    // the rectangle in the grid
+    min: Pos<usize>,
+    size: Pos<usize>,
+    // positions in the view are transposed first, then mirrored within the rectangle
+    flip_x: bool,
+    flip_y: bool,
+    transposed: bool,
+}
+
+impl<T, PT> Clone for View<'_, T, PT> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, PT> Copy for View<'_, T, PT> {}
+
+impl<'a, T, PT: Coordinate> View<'a, T, PT> {
+    pub fn size(self) -> Pos<usize> {
+        if self.transposed {
+            Pos {
+                x: self.size.y,
+                y: self.size.x,
+            }
+        } else {
+            self.size
+        }
+    }
+    fn to_grid(self, pos: Pos<usize>) -> Pos<usize> {
+        let (x, y) = if self.transposed {
+            (pos.y, pos.x)
+        } else {
+            (pos.x, pos.y)
+        };
+        Pos {
+            x: self.min.x + if self.flip_x { self.size.x - 1 - x } else { x },
+            y: self.min.y + if self.flip_y { self.size.y - 1 - y } else { y },
+        }
+    }
+    pub fn transpose(self) -> Self {
+        View {
+            transposed: !self.transposed,
+            ..self
+        }
+    }
+    // mirrors left and right
+    pub fn flip(self) -> Self {
+        if self.transposed {
+            View {
+                flip_y: !self.flip_y,
+                ..self
+            }
+        } else {
+            View {
+                flip_x: !self.flip_x,
+                ..self
+            }
+        }
+    }
+    // a quarter turn clockwise
+    pub fn rotate(self) -> Self {
+        self.transpose().flip()
+    }
+    // the rectangle of size at min, None if it is empty or does not fit in the view
+    pub fn sub(self, min: Pos<usize>, size: Pos<usize>) -> Option<Self> {
+        let view_size = self.size();
+        let fits = |min: usize, size: usize, view_size: usize| {
+            size > 0 && min.checked_add(size).is_some_and(|end| end <= view_size)
+        };
+        if !fits(min.x, size.x, view_size.x) || !fits(min.y, size.y, view_size.y) {
+            return None;
+        }
+        let (a, b) = (
+            self.to_grid(min),
+            self.to_grid(Pos {
+                x: min.x + size.x - 1,
+                y: min.y + size.y - 1,
+            }),
+        );
+        Some(View {
+            min: Pos {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            size: Pos {
+                x: a.x.abs_diff(b.x) + 1,
+                y: a.y.abs_diff(b.y) + 1,
+            },
+            ..self
+        })
+    }
+    pub fn rows(self) -> impl Iterator<Item = Self> {
+        let size = self.size();
+        (0..size.y).filter_map(move |y| self.sub(Pos { x: 0, y }, Pos { x: size.x, y: 1 }))
+    }
+    // the values with their positions in the grid, row by row
+    pub fn cells(self) -> impl Iterator<Item = (Pos<PT>, &'a T)> {
+        let size = self.size();
+        (0..size.x * size.y).map(move |index| {
+            let pos = self.to_grid(Pos {
+                x: index % size.x,
+                y: index / size.x,
+            });
+            let value = &self.grid.values[pos.x + pos.y * self.grid.width];
+            // cannot fail, the positions are inside the grid
+            let pos = Pos {
+                x: PT::from_index(pos.x).unwrap(),
+                y: PT::from_index(pos.y).unwrap(),
+            };
+            (pos, value)
+        })
+    }
+    pub fn values(self) -> impl Iterator<Item = &'a T> {
+        self.cells().map(|(_, value)| value)
+    }
+}
+
+// A grid addressed in world coordinates, covering a rectangle anywhere, also at negative
+// coordinates
+#[derive(Clone)]
+pub struct OffsetGrid<T, PT> {
+    grid: Grid<T, PT>,
+    min: Pos<PT>,
+    max: Pos<PT>,
+}
+
+impl<T, PT: Coordinate> OffsetGrid<T, PT> {
+    // covers min to max, both included
+    pub fn new(value: T, min: Pos<PT>, max: Pos<PT>) -> Result<Self, AocError>
+    where
+        T: Clone,
+    {
+        let side = |min: PT, max: PT| PT::from_i128(max.to_i128() - min.to_i128() + 1);
+        let size = side(min.x, max.x)
+            .zip(side(min.y, max.y))
+            .map(|(x, y)| Pos { x, y })
+            .ok_or_else(|| {
+                format!(
+                    "Cannot make a grid from {},{} to {},{}",
+                    min.x, min.y, max.x, max.y
+                )
+            })?;
+        Ok(OffsetGrid {
+            grid: Grid::from_value(value, size)?,
+            min,
+            max,
+        })
+    }
+    // covers all points with a margin on every side
+    pub fn around(
+        value: T,
+        points: impl IntoIterator<Item = Pos<PT>>,
+        margin: Pos<PT>,
+    ) -> Result<Self, AocError>
+    where
+        T: Clone,
+    {
+        let (min, max) = points
+            .into_iter()
+            .fold(None, |bounds, p| Some(cover(bounds, p)))
+            .ok_or("No points to put a grid around")?;
+        let widen = |value: PT, margin: PT, sign: i128| {
+            PT::from_i128(value.to_i128() + sign * margin.to_i128())
+                .ok_or_else(|| format!("A margin of {margin} around {value} does not fit"))
+        };
+        let min = Pos {
+            x: widen(min.x, margin.x, -1)?,
+            y: widen(min.y, margin.y, -1)?,
+        };
+        let max = Pos {
+            x: widen(max.x, margin.x, 1)?,
+            y: widen(max.y, margin.y, 1)?,
+        };
+        OffsetGrid::new(value, min, max)
+    }
+    fn local(&self, pos: &Pos<PT>) -> Option<Pos<PT>> {
+        Some(Pos {
+            x: pos.x.minus(self.min.x)?,
+            y: pos.y.minus(self.min.y)?,
+        })
+    }
+}
+
+// A grid without bounds that grows when written to, everywhere else it holds the default
+#[derive(Clone)]
+pub struct SparseGrid<T, PT> {
+    cells: HashMap<Pos<PT>, T>,
+    default: T,
+    bounds: Option<(Pos<PT>, Pos<PT>)>,
+}
+
+impl<T, PT: Coordinate> SparseGrid<T, PT> {
+    pub fn new(default: T) -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+            default,
+            bounds: None,
+        }
+    }
+    // the values that were written, in no particular order
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.values()
+    }
+}
+
+impl<T, PT: Coordinate> GridAccess<T, PT> for Grid<T, PT> {
+    fn get(&self, pos: &Pos<PT>) -> Option<&T> {
+        self.index_of(pos).map(|index| &self.values[index])
+    }
+    fn get_mut(&mut self, pos: &Pos<PT>) -> Option<&mut T> {
+        self.index_of(pos).map(|index| &mut self.values[index])
+    }
+    fn bounds(&self) -> Option<(Pos<PT>, Pos<PT>)> {
+        let corner = |index: usize| PT::from_index(index).unwrap(); // cannot fail, within the size
+        (!self.values.is_empty()).then(|| {
+            (
+                Pos {
+                    x: corner(0),
+                    y: corner(0),
+                },
+                Pos {
+                    x: corner(self.width - 1),
+                    y: corner(self.height - 1),
+                },
+            )
+        })
+    }
+}
+
+impl<T, PT: Coordinate> GridAccess<T, PT> for OffsetGrid<T, PT> {
+    fn get(&self, pos: &Pos<PT>) -> Option<&T> {
+        self.grid.get(&self.local(pos)?)
+    }
+    fn get_mut(&mut self, pos: &Pos<PT>) -> Option<&mut T> {
+        let local = self.local(pos)?;
+        self.grid.get_mut(&local)
+    }
+    fn bounds(&self) -> Option<(Pos<PT>, Pos<PT>)> {
+        Some((self.min, self.max))
+    }
+}
+
+impl<T: Clone, PT: Coordinate> GridAccess<T, PT> for SparseGrid<T, PT> {
+    fn get(&self, pos: &Pos<PT>) -> Option<&T> {
+        Some(self.cells.get(pos).unwrap_or(&self.default))
+    }
+    // writing grows the grid, so there always is a value
+    fn get_mut(&mut self, pos: &Pos<PT>) -> Option<&mut T> {
+        self.bounds = Some(cover(self.bounds, *pos));
+        Some(
+            self.cells
+                .entry(*pos)
+                .or_insert_with(|| self.default.clone()),
+        )
+    }
+    fn bounds(&self) -> Option<(Pos<PT>, Pos<PT>)> {
+        self.bounds
+    }
+}
+
+// A parsed character map with the positions of its marker characters
+pub struct CharMap<T> {
+    pub grid: Grid<T, i16>,
+    markers: Vec<(char, Pos<i16>)>,
+}
+
+impl<T> CharMap<T> {
+    // the first position of a marker character
+    pub fn marker(&self, marker: char) -> Result<Pos<i16>, AocError> {
+        self.markers
+            .iter()
+            .find(|(c, _)| *c == marker)
+            .map(|(_, pos)| *pos)
+            .ok_or_else(|| format!("No '{marker}' in the grid").into())
+    }
 }
 
 impl<T: Clone> Grid<T, i16> {
-    pub fn from_value(value: T, size: Pos<i16>) -> Self {
-        Grid {
-            values: vec![value; size.x as usize * size.y as usize],
-            size,
+    // Parses a map with one character per cell, turning every character into a value. All
+    // rows must be equally long. A border of `width` cells of `fill` around the map saves
+    // bounds checks, positions then include the border. The positions of the `markers`
+    // characters are kept, see CharMap::marker.
+    pub fn parse(
+        input: &str,
+        border: Option<(i16, T)>,
+        markers: &str,
+        mut value: impl FnMut(char) -> Result<T, AocError>,
+    ) -> Result<CharMap<T>, AocError> {
+        let lines: Vec<&str> = input.lines().collect();
+        let columns = lines.first().map_or(0, |line| line.chars().count());
+        if columns == 0 {
+            Err("The grid is empty")?;
+        }
+        let width = border.as_ref().map_or(0, |(width, _)| *width);
+        let size = Pos {
+            x: i16::try_from(columns)? + 2 * width,
+            y: i16::try_from(lines.len())? + 2 * width,
+        };
+        let mut values = Vec::with_capacity(size.x as usize * size.y as usize);
+        let pad = |values: &mut Vec<T>, cells: i16| {
+            if let Some((_, fill)) = &border {
+                values.extend(repeat_n(fill.clone(), cells as usize));
+            }
+        };
+        let mut found = Vec::new();
+        pad(&mut values, width * size.x);
+        for (y, line) in lines.iter().enumerate() {
+            let found_columns = line.chars().count();
+            if found_columns != columns {
+                let message = format!("Expected {columns} columns, found {found_columns}");
+                return Err(AocError::from(message).at_line(y + 1, line));
+            }
+            pad(&mut values, width);
+            for (x, c) in line.chars().enumerate() {
+                if markers.contains(c) {
+                    let pos = Pos {
+                        x: x as i16 + width,
+                        y: y as i16 + width,
+                    };
+                    found.push((c, pos));
+                }
+                values.push(value(c).map_err(|e| AocError::Parse {
+                    line: Some(y + 1),
+                    column: Some(x + 1),
+                    snippet: line.to_string(),
+                    message: e.to_string(),
+                })?);
+            }
+            pad(&mut values, width);
         }
+        pad(&mut values, width * size.x);
+        Ok(CharMap {
+            grid: Grid::from_values(values, size)?,
+            markers: found,
+        })
+    }
+}
+
+impl<T, PT: Coordinate> Index<&Pos<PT>> for Grid<T, PT> {
+    type Output = T;
+    fn index(&self, pos: &Pos<PT>) -> &Self::Output {
+        let size = self.size;
+        self.get(pos).unwrap_or_else(|| {
+            panic!(
+                "{},{} is outside the {}x{} grid",
+                pos.x, pos.y, size.x, size.y
+            )
+        })
+    }
+}
+
+impl<T, PT: Coordinate> IndexMut<&Pos<PT>> for Grid<T, PT> {
+    fn index_mut(&mut self, pos: &Pos<PT>) -> &mut Self::Output {
+        let size = self.size;
+        self.get_mut(pos).unwrap_or_else(|| {
+            panic!(
+                "{},{} is outside the {}x{} grid",
+                pos.x, pos.y, size.x, size.y
+            )
+        })
+    }
+}
+
+impl<T, PT: Coordinate> Index<&Pos<PT>> for OffsetGrid<T, PT> {
+    type Output = T;
+    fn index(&self, pos: &Pos<PT>) -> &Self::Output {
+        let (min, max) = (self.min, self.max);
+        self.get(pos).unwrap_or_else(|| {
+            panic!(
+                "{},{} is outside the grid from {},{} to {},{}",
+                pos.x, pos.y, min.x, min.y, max.x, max.y
+            )
+        })
     }
 }
 
-impl<T> Grid<T, i16> {
-    pub unsafe fn get_unchecked(&self, index: &Pos<i16>) -> &T {
-        self.values
-            .get_unchecked(index.x as usize + index.y as usize * self.size.x as usize)
+impl<T, PT: Coordinate> IndexMut<&Pos<PT>> for OffsetGrid<T, PT> {
+    fn index_mut(&mut self, pos: &Pos<PT>) -> &mut Self::Output {
+        let (min, max) = (self.min, self.max);
+        self.get_mut(pos).unwrap_or_else(|| {
+            panic!(
+                "{},{} is outside the grid from {},{} to {},{}",
+                pos.x, pos.y, min.x, min.y, max.x, max.y
+            )
+        })
     }
 }
 
-impl<T> Index<&Pos<i16>> for Grid<T, i16> {
+impl<T: Clone, PT: Coordinate> Index<&Pos<PT>> for SparseGrid<T, PT> {
     type Output = T;
-    fn index(&self, index: &Pos<i16>) -> &Self::Output {
-        &self.values[index.x as usize + index.y as usize * self.size.x as usize]
+    fn index(&self, pos: &Pos<PT>) -> &Self::Output {
+        self.cells.get(pos).unwrap_or(&self.default)
     }
 }
 
-impl<T> IndexMut<&Pos<i16>> for Grid<T, i16> {
-    fn index_mut(&mut self, index: &Pos<i16>) -> &mut Self::Output {
-        &mut self.values[index.x as usize + index.y as usize * self.size.x as usize]
+impl<T: Clone, PT: Coordinate> IndexMut<&Pos<PT>> for SparseGrid<T, PT> {
+    fn index_mut(&mut self, pos: &Pos<PT>) -> &mut Self::Output {
+        self.get_mut(pos).unwrap() // cannot fail, the grid grows
     }
 }
 
-impl<T: Display> Display for Grid<T, i16> {
+impl<T: Display, PT> Display for Grid<T, PT> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for line in self.values.chunks(self.size.x as usize) {
+        for line in self.values.chunks(self.width.max(1)) {
             for t in line {
                 write!(f, "{t:3}")?;
             }
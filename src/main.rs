@@ -1,7 +1,21 @@
-use std::{error::Error, time::Instant};
+use std::{
+    env::args,
+    error::Error,
+    io::{stdout, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
 
+use context::Context;
+use random::Rng;
 use runner::Runner;
+use secret::{read_input, Key};
 
+mod answers;
+mod context;
+mod difftest;
+mod error;
+mod fuzz;
 mod grid;
 mod optimize;
 mod parsing;
@@ -27,19 +41,168 @@ mod q18;
 mod q19;
 mod q20;
 mod q21;
+mod random;
+mod report;
 mod runner;
+mod secret;
+mod trace;
+mod usage;
 
 macro_rules! run {
     ($runner:ident $mod:ident) => {
+        run!($runner $mod, |i, _| $mod::a(i), |i, _| $mod::b(i))
+    };
+    ($runner:ident $mod:ident with context) => {
+        run!($runner $mod, |i, ctx| $mod::a(i, ctx), |i, ctx| $mod::b(i, ctx))
+    };
+    ($runner:ident $mod:ident, $a:expr, $b:expr) => {
         let name = stringify!($mod);
-        $runner.run_test(&format!("{name}::a"), $mod::a, &format!("{name}.real"));
-        $runner.run_test(&format!("{name}::b"), $mod::b, &format!("{name}.real"));
+        $runner.run_test(&format!("{name}::a"), $a, &format!("{name}.real"));
+        $runner.run_test(&format!("{name}::b"), $b, &format!("{name}.real"));
     };
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let runner = Runner::with_data_dir("data")?;
-    let start = Instant::now();
+// generate <day> [<size> [<seed>]] writes a synthetic input for a day to stdout
+fn generate(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let day = args.first().ok_or("Expected a day like q01")?;
+    let size = args.get(1).map_or(Ok(1000), |s| s.parse())?;
+    let seed = args.get(2).map_or(Ok(0), |s| s.parse())?;
+    let mut rng = Rng::new(seed);
+    let mut out = BufWriter::new(stdout().lock());
+    macro_rules! generate {
+        ($($mod:ident)*) => {
+            match day.as_str() {
+                $(stringify!($mod) => $mod::generate(&mut rng, size, &mut out)?,)*
+                _ => Err(format!("Unknown day {day}"))?,
+            }
+        };
+    }
+    generate!(q01 q02 q03 q04 q05 q06 q07 q08 q09 q10 q11 q12 q13 q14 q15 q16 q17 q18 q19 q20 q21);
+    out.flush()?;
+    Ok(())
+}
+
+// difftest [<filter> [<runs> [<seed>]]] checks solvers against their references
+fn difftest(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let filter = args.first().map_or("", String::as_str);
+    let runs = args.get(1).map_or(Ok(20), |s| s.parse())?;
+    let seed = args.get(2).map_or(Ok(0), |s| s.parse())?;
+    // the solvers panic on plenty of malformed inputs while minimizing
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut failures = 0;
+    macro_rules! difftest {
+        ($mod:ident) => {
+            difftest!($mod, |i: &[u8]| $mod::a(i), |i: &[u8]| $mod::b(i))
+        };
+        ($mod:ident with context) => {
+            difftest!(
+                $mod,
+                |i: &[u8]| $mod::a(i, &Context::new()),
+                |i: &[u8]| $mod::b(i, &Context::new())
+            )
+        };
+        ($mod:ident, $a:expr, $b:expr) => {
+            let name = stringify!($mod);
+            if format!("{name}::a").starts_with(filter)
+                && !difftest::compare(
+                    &format!("{name}::a"),
+                    $mod::generate,
+                    $a,
+                    |i: &[u8]| $mod::reference_a(i),
+                    runs,
+                    seed,
+                )?
+            {
+                failures += 1;
+            }
+            if format!("{name}::b").starts_with(filter)
+                && !difftest::compare(
+                    &format!("{name}::b"),
+                    $mod::generate,
+                    $b,
+                    |i: &[u8]| $mod::reference_b(i),
+                    runs,
+                    seed,
+                )?
+            {
+                failures += 1;
+            }
+        };
+    }
+    difftest!(q01);
+    difftest!(q02);
+    difftest!(q03);
+    difftest!(q04);
+    difftest!(q05);
+    difftest!(q06);
+    difftest!(q07);
+    difftest!(q08);
+    difftest!(q09);
+    difftest!(q10);
+    difftest!(q11);
+    difftest!(q12);
+    difftest!(q13);
+    difftest!(q14);
+    difftest!(q15, |i: &[u8]| q15::a(i), |i: &[u8]| q15::b(
+        i,
+        &Context::new()
+    ));
+    difftest!(q16 with context);
+    difftest!(q17);
+    difftest!(q18);
+    difftest!(q19 with context);
+    difftest!(q20);
+    difftest!(q21);
+    std::panic::set_hook(hook);
+    if failures > 0 {
+        Err(format!("{failures} solvers disagree with their reference"))?;
+    }
+    Ok(())
+}
+
+// fuzz [<filter> [<runs> [<seed>]]] feeds mutated real and generated inputs to the parsers
+fn fuzz(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let filter = args.first().map_or("", String::as_str);
+    let runs = args.get(1).map_or(Ok(1000), |s| s.parse())?;
+    let seed = args.get(2).map_or(Ok(0), |s| s.parse())?;
+    let data_dir = Path::new("data");
+    let key = Key::load(data_dir)?;
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let mut failures = 0;
+    macro_rules! fuzz {
+        ($($mod:ident)*) => {
+            $(
+                let name = stringify!($mod);
+                if name.starts_with(filter) {
+                    let mut corpus = Vec::new();
+                    let real = data_dir.join(format!("{name}.real"));
+                    if let Ok(input) = read_input(&real, key.as_ref()) {
+                        corpus.push(input);
+                    }
+                    for size in [3, 10] {
+                        let mut input = Vec::new();
+                        $mod::generate(&mut Rng::new(seed), size, &mut input)?;
+                        corpus.push(input);
+                    }
+                    if !fuzz::fuzz(name, |i| $mod::reformat(i), &corpus, runs, seed) {
+                        failures += 1;
+                    }
+                }
+            )*
+        };
+    }
+    // q06 reads its input byte by byte, there is no parser to fuzz
+    fuzz!(q01 q02 q03 q04 q05 q07 q08 q09 q10 q11 q12 q13 q14 q15 q16 q17 q18 q19 q20 q21);
+    std::panic::set_hook(hook);
+    if failures > 0 {
+        Err(format!("{failures} parsers panicked or got stuck"))?;
+    }
+    Ok(())
+}
+
+fn run_all(runner: &Runner) {
     run!(runner q01);
     run!(runner q02);
     run!(runner q03);
@@ -54,15 +217,48 @@ fn main() -> Result<(), Box<dyn Error>> {
     run!(runner q12);
     run!(runner q13);
     run!(runner q14);
-    run!(runner q15);
-    run!(runner q16);
+    run!(runner q15, |i, _| q15::a(i), q15::b);
+    run!(runner q16 with context);
     run!(runner q17);
     run!(runner q18);
-    run!(runner q19);
+    run!(runner q19 with context);
     run!(runner q20);
     run!(runner q21);
     // runner.run_test("test", q21::b, "test");
-    let elapsed = start.elapsed();
-    println!("Ran all puzzles in {}ms", elapsed.as_millis());
+}
+
+// report [<file>] runs every puzzle and writes an HTML report, report.html by default
+fn report(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let path = args.first().map_or("report.html", String::as_str);
+    let data_dir = Path::new("data");
+    let runner = Runner::with_data_dir(data_dir, &[])?;
+    run_all(&runner);
+    let results = runner.into_results();
+    report::append_history(data_dir, &results)?;
+    report::write_report(Path::new(path), &results, &report::load_history(data_dir)?)?;
+    println!("Wrote {path}");
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("generate") => return generate(&args[1..]),
+        Some("difftest") => return difftest(&args[1..]),
+        Some("fuzz") => return fuzz(&args[1..]),
+        Some("keygen") => return Key::generate(Path::new("data")),
+        Some("encrypt") => return secret::encrypt_files(Path::new("data"), &args[1..]),
+        Some("decrypt") => return secret::decrypt_files(Path::new("data"), &args[1..]),
+        Some("report") => return report(&args[1..]),
+        Some("hash-answers") => return answers::hash_answers(Path::new("data")),
+        Some("reveal") => {
+            return answers::reveal(Path::new("data"), args.get(1).map(String::as_str))
+        }
+        _ => {}
+    }
+    let runner = Runner::with_data_dir("data", &args)?;
+    let start = Instant::now();
+    run_all(&runner);
+    runner.finish(start.elapsed());
     Ok(())
 }
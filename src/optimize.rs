@@ -1,5 +1,7 @@
 use std::collections::BinaryHeap;
 
+use crate::context::{Cancelled, Context};
+
 struct OrdByFirst<Prio, State>(Prio, State);
 
 impl<Prio: PartialEq, State> PartialEq for OrdByFirst<Prio, State> {
@@ -42,13 +44,26 @@ pub trait Optimize {
     }
 }
 
-pub fn optimize<P: Optimize>(problem: &P, initial: P::State) -> P::State {
+// how many states are expanded between checks for cancellation
+const CHECK_INTERVAL: u64 = 4096;
+
+pub fn optimize<P: Optimize>(
+    problem: &P,
+    initial: P::State,
+    ctx: &Context,
+) -> Result<P::State, Cancelled> {
     let mut queue = BinaryHeap::new();
     queue.push(OrdByFirst(problem.potential(&initial), initial.clone()));
     let mut best_solution = (problem.guaranteed(&initial), initial);
     let mut next = Vec::new();
     let mut solutions = P::Solutions::default();
+    let mut expanded = 0;
     while let Some(OrdByFirst(_, state)) = queue.pop() {
+        expanded += 1;
+        if expanded % CHECK_INTERVAL == 0 {
+            ctx.add_states(CHECK_INTERVAL);
+            ctx.check()?;
+        }
         if problem.add_if_improvement(&mut solutions, &state) {
             let guaranteed = problem.guaranteed(&state);
             if guaranteed > best_solution.0 {
@@ -63,5 +78,6 @@ pub fn optimize<P: Optimize>(problem: &P, initial: P::State) -> P::State {
             }
         }
     }
-    best_solution.1
+    ctx.add_states(expanded % CHECK_INTERVAL);
+    Ok(best_solution.1)
 }
@@ -1,58 +1,684 @@
 use itertools::Itertools;
-use std::io::BufRead;
+use std::any::type_name;
+use std::fmt::Display;
+use std::io::{BufRead, Read, Write};
 use std::marker::PhantomData;
 use std::str::FromStr;
 
+pub mod combinators;
+
+use crate::error::AocError;
+
 pub trait FromBufRead: Sized {
     type Error;
     fn read(br: &mut impl BufRead) -> Result<Self, Self::Error>;
 
-    fn read_iter<B: BufRead>(buf_read: &mut B) -> ParseIter<B, Self> {
+    fn read_iter<B: BufRead>(buf_read: &mut B) -> ParseIter<'_, B, Self> {
         ParseIter {
-            buf_read,
+            buf_read: Tracked {
+                inner: buf_read,
+                line: 1,
+                record: Vec::new(),
+            },
+            failed: false,
             _t: PhantomData,
         }
     }
 }
 
+// Keeps what a record consumed and on which line it started, so that a failing record can be
+// reported with the line it failed on
+struct Tracked<'a, B> {
+    inner: &'a mut B,
+    line: usize,
+    record: Vec<u8>,
+}
+
+impl<B: BufRead> Tracked<'_, B> {
+    fn start_record(&mut self) {
+        self.line += self.record.iter().filter(|b| **b == b'\n').count();
+        self.record.clear();
+    }
+
+    // the last line the record read from, which is the one parsing failed on
+    fn error(&self, error: AocError) -> AocError {
+        let record = self.record.strip_suffix(b"\n").unwrap_or(&self.record);
+        let newlines = record.iter().filter(|b| **b == b'\n').count();
+        let text = record.rsplit(|b| *b == b'\n').next().unwrap_or_default();
+        error.at_line(self.line + newlines, &String::from_utf8_lossy(text))
+    }
+}
+
+impl<B: BufRead> Read for Tracked<'_, B> {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<B: BufRead> BufRead for Tracked<'_, B> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+    fn consume(&mut self, amount: usize) {
+        // the bytes being consumed are still buffered, so this does no I/O and cannot fail
+        if let Ok(buffered) = self.inner.fill_buf() {
+            self.record
+                .extend_from_slice(&buffered[..amount.min(buffered.len())]);
+        }
+        self.inner.consume(amount);
+    }
+}
+
+// Reads the whole input as a single T, with the line number in any error
+pub fn read_all<T: FromBufRead>(br: &mut impl BufRead) -> Result<T, AocError>
+where
+    T::Error: Into<AocError>,
+{
+    let mut tracked = Tracked {
+        inner: br,
+        line: 1,
+        record: Vec::new(),
+    };
+    let value = T::read(&mut tracked).map_err(|e| tracked.error(e.into()))?;
+    if !tracked.fill_buf()?.is_empty() {
+        tracked.start_record();
+        tracked.read_line(&mut String::new())?;
+        Err(tracked.error("Unexpected input after the end".into()))?;
+    }
+    Ok(value)
+}
+
 pub struct ParseIter<'a, BR: 'a + BufRead, T: FromBufRead> {
-    buf_read: &'a mut BR,
+    buf_read: Tracked<'a, BR>,
+    failed: bool,
     _t: PhantomData<T>,
 }
 
-impl<'a, BR: 'a + BufRead, T: FromBufRead> Iterator for ParseIter<'a, BR, T> {
-    type Item = Result<T, T::Error>;
+impl<'a, BR: 'a + BufRead, T: FromBufRead> Iterator for ParseIter<'a, BR, T>
+where
+    T::Error: Into<AocError>,
+{
+    type Item = Result<T, AocError>;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        self.buf_read.start_record();
         match self.buf_read.fill_buf() {
-            Err(_) => None,
+            Err(e) => {
+                // a failed read would most likely fail again, so it ends the iteration
+                self.failed = true;
+                Some(Err(e.into()))
+            }
             Ok(&[]) => None,
-            Ok(_) => Some(T::read(self.buf_read)),
+            Ok(_) => Some(T::read(&mut self.buf_read).map_err(|e| self.buf_read.error(e.into()))),
+        }
+    }
+}
+
+// Inputs made of blocks separated by blank lines can be described by types: Line<T> is one
+// line parsed with FromStr, Vec<T> and Option<T> read until the current block ends, arrays
+// and tuples read a fixed number of items and Section<T> reads T and the blank line after it.
+// Section::<(Line<Packet>, Line<Packet>)>::read_iter then reads pairs of packets.
+fn at_section_end(br: &mut impl BufRead) -> std::io::Result<bool> {
+    Ok(matches!(br.fill_buf()?, [] | [b'\n', ..]))
+}
+
+pub struct Line<T>(pub T);
+
+impl<T: FromStr> FromBufRead for Line<T>
+where
+    T::Err: Into<AocError>,
+{
+    type Error = AocError;
+    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+        let mut line = String::new();
+        if br.read_line(&mut line)? == 0 {
+            Err("Expected a line, found the end of the input")?;
+        }
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        Ok(Line(line.parse().map_err(Into::into)?))
+    }
+}
+
+pub struct Section<T>(pub T);
+
+impl<T: FromBufRead> FromBufRead for Section<T>
+where
+    T::Error: Into<AocError>,
+{
+    type Error = AocError;
+    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+        let section = T::read(br).map_err(Into::into)?;
+        match br.fill_buf()? {
+            [] => {}
+            [b'\n', ..] => br.consume(1),
+            _ => Err("Expected a blank line after the section")?,
+        }
+        Ok(Section(section))
+    }
+}
+
+// every section of the input read as a T
+pub fn read_sections<'a, T: FromBufRead + 'a>(
+    br: &'a mut impl BufRead,
+) -> impl Iterator<Item = Result<T, AocError>> + 'a
+where
+    T::Error: Into<AocError>,
+{
+    Section::<T>::read_iter(br).map_ok(|section| section.0)
+}
+
+impl<T: FromBufRead> FromBufRead for Vec<T>
+where
+    T::Error: Into<AocError>,
+{
+    type Error = AocError;
+    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+        let mut items = Vec::new();
+        while !at_section_end(br)? {
+            items.push(T::read(br).map_err(Into::into)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: FromBufRead> FromBufRead for Option<T>
+where
+    T::Error: Into<AocError>,
+{
+    type Error = AocError;
+    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+        if at_section_end(br)? {
+            return Ok(None);
+        }
+        Ok(Some(T::read(br).map_err(Into::into)?))
+    }
+}
+
+impl<T: FromBufRead, const N: usize> FromBufRead for [T; N]
+where
+    T::Error: Into<AocError>,
+{
+    type Error = AocError;
+    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+        let mut items = Vec::with_capacity(N);
+        for _ in 0..N {
+            if at_section_end(br)? {
+                Err(format!(
+                    "Expected {N} items, the section ended after {}",
+                    items.len()
+                ))?;
+            }
+            items.push(T::read(br).map_err(Into::into)?);
         }
+        // exactly N items were read
+        Ok(items.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+}
+
+macro_rules! from_buf_read_tuple {
+    ($($t:ident),*) => {
+        impl<$($t: FromBufRead),*> FromBufRead for ($($t,)*)
+        where
+            $($t::Error: Into<AocError>),*
+        {
+            type Error = AocError;
+            fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
+                Ok(($($t::read(br).map_err(Into::into)?,)*))
+            }
+        }
+    };
+}
+
+from_buf_read_tuple!(A, B);
+from_buf_read_tuple!(A, B, C);
+from_buf_read_tuple!(A, B, C, D);
+
+// The mirror of FromBufRead: writes a value in the format it is read from, so that reading
+// what was written gives the same value again. Line<T> writes T with Display, the mirror of
+// FromStr, and Section<T> writes the blank line that ends it.
+pub trait ToBufWrite {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()>;
+}
+
+impl<T: Display> ToBufWrite for Line<T> {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        writeln!(bw, "{}", self.0)
+    }
+}
+
+impl<T: ToBufWrite> ToBufWrite for Section<T> {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        self.0.write(bw)?;
+        writeln!(bw)
+    }
+}
+
+impl<T: ToBufWrite> ToBufWrite for Vec<T> {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        self.iter().try_for_each(|item| item.write(bw))
+    }
+}
+
+impl<T: ToBufWrite> ToBufWrite for Option<T> {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        self.iter().try_for_each(|item| item.write(bw))
     }
 }
 
-pub fn parse_by_line<T: FromStr>(
-    buf: impl BufRead,
-) -> impl Iterator<Item = Result<Result<T, T::Err>, std::io::Error>> {
-    buf.lines().map_ok(|l| T::from_str(&l))
+impl<T: ToBufWrite, const N: usize> ToBufWrite for [T; N] {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        self.iter().try_for_each(|item| item.write(bw))
+    }
+}
+
+macro_rules! to_buf_write_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: ToBufWrite),*> ToBufWrite for ($($t,)*) {
+            fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+                $(self.$i.write(bw)?;)*
+                Ok(())
+            }
+        }
+    };
 }
 
-// this macro will only be able to parse a regex with a fixed number of (non-optional) groups.
-// it doesn't work with &str and is inefficient with String
+to_buf_write_tuple!(A 0, B 1);
+to_buf_write_tuple!(A 0, B 1, C 2);
+to_buf_write_tuple!(A 0, B 1, C 2, D 3);
+
+// the mirror of read_sections, the sections are separated by a blank line
+pub fn write_sections<'a, T: ToBufWrite + 'a>(
+    sections: impl IntoIterator<Item = &'a T>,
+    bw: &mut impl Write,
+) -> std::io::Result<()> {
+    for (index, section) in sections.into_iter().enumerate() {
+        if index > 0 {
+            writeln!(bw)?;
+        }
+        section.write(bw)?;
+    }
+    Ok(())
+}
+
+// the mirror of parse_by_line and parse_lines
+pub fn write_lines<T: Display>(
+    items: impl IntoIterator<Item = T>,
+    bw: &mut impl Write,
+) -> std::io::Result<()> {
+    items
+        .into_iter()
+        .try_for_each(|item| writeln!(bw, "{item}"))
+}
+
+// The runner reads the whole input once and every solver takes it as a &[u8], so they can
+// parse from views into it instead of allocating a String for every line. The days that are
+// parsed with FromBufRead (q05, q11, q13 and q17) still read the slice as a BufRead.
+pub fn input_str(input: &[u8]) -> Result<&str, AocError> {
+    Ok(std::str::from_utf8(input)?)
+}
+
+pub fn parse_lines<'a, T: FromStr + 'a>(
+    input: &'a str,
+) -> impl Iterator<Item = Result<T, AocError>> + 'a
+where
+    T::Err: Into<AocError>,
+{
+    parse_lines_from(input, 1)
+}
+
+// input does not have to start at the first line, errors report line numbers from first_line
+fn parse_lines_from<'a, T: FromStr + 'a>(
+    input: &'a str,
+    first_line: usize,
+) -> impl Iterator<Item = Result<T, AocError>> + 'a
+where
+    T::Err: Into<AocError>,
+{
+    input.lines().enumerate().map(move |(index, line)| {
+        T::from_str(line).map_err(|e| e.into().at_line(first_line + index, line))
+    })
+}
+
+// smaller chunks are not worth starting a thread for
+const MIN_CHUNK_SIZE: usize = 1 << 16;
+
+// Same result as parse_lines, but the input is split at line boundaries and the chunks are
+// parsed on their own threads. The first error in the input is reported, not the first one
+// any thread happens to find.
+pub fn parse_lines_parallel<T: FromStr + Send>(input: &str) -> Result<Vec<T>, AocError>
+where
+    T::Err: Into<AocError>,
+{
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(input.len() / MIN_CHUNK_SIZE)
+        .max(1);
+    parse_lines_in_chunks(input, input.len().div_ceil(threads))
+}
+
+// every chunk is at least chunk_size bytes, up to the end of its last line
+fn parse_lines_in_chunks<T: FromStr + Send>(
+    input: &str,
+    chunk_size: usize,
+) -> Result<Vec<T>, AocError>
+where
+    T::Err: Into<AocError>,
+{
+    // (first line number, whole lines)
+    let mut chunks = Vec::new();
+    let mut rest = input;
+    let mut first_line = 1;
+    while !rest.is_empty() {
+        let end = match rest.as_bytes()[chunk_size.min(rest.len())..]
+            .iter()
+            .position(|b| *b == b'\n')
+        {
+            // just after a newline is always a char boundary
+            Some(newline) => chunk_size + newline + 1,
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push((first_line, chunk));
+        first_line += chunk.bytes().filter(|b| *b == b'\n').count();
+        rest = tail;
+    }
+    std::thread::scope(|scope| {
+        let handles = chunks
+            .into_iter()
+            .map(|(first_line, chunk)| {
+                scope.spawn(move || -> Result<Vec<T>, AocError> {
+                    parse_lines_from(chunk, first_line).collect()
+                })
+            })
+            .collect_vec();
+        let mut values = Vec::new();
+        for handle in handles {
+            let chunk = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            values.extend(chunk?);
+        }
+        Ok(values)
+    })
+}
+
+// blocks of lines separated by blank lines, without their final newline
+pub fn sections(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split("\n\n")
+        .map(|section| section.trim_end_matches('\n'))
+        .filter(|section| !section.is_empty())
+}
+
+// How regex_parse! turns a capture group into a value. A group that did not participate in
+// the match is None, which is only allowed for Option outputs.
+pub trait FromCapture<'a>: Sized {
+    fn from_capture(capture: Option<&'a str>) -> Result<Self, AocError>;
+}
+
+impl<'a> FromCapture<'a> for &'a str {
+    fn from_capture(capture: Option<&'a str>) -> Result<Self, AocError> {
+        Ok(capture.ok_or("did not match")?)
+    }
+}
+
+impl<'a, T: FromCapture<'a>> FromCapture<'a> for Option<T> {
+    fn from_capture(capture: Option<&'a str>) -> Result<Self, AocError> {
+        capture.map(|c| T::from_capture(Some(c))).transpose()
+    }
+}
+
+pub fn parse_capture<T: FromStr>(capture: Option<&str>) -> Result<T, AocError>
+where
+    T::Err: Display,
+{
+    let capture = capture.ok_or("did not match")?;
+    capture
+        .parse()
+        .map_err(|e| format!("could not parse '{capture}' as {}: {e}", type_name::<T>()).into())
+}
+
+// implements FromCapture through FromStr, for types that regex_parse! should parse
 #[macro_export]
-macro_rules! regex_parse {
-    ($reg:ident, $text:ident, ($t0:ty,$($t:ty),*)) => {
-        match $reg.captures($text) {
-            None => None,
-            Some(captures) => {
-                // if any 'unwrap' panics, there is a logic error in the code or the regex
-                let mut iter = captures.iter().skip(1).map(|m| m.unwrap().as_str());
-                let mut wrap = || -> Result<($t0,$($t),*), Box<dyn Error>> {
-                    let result: ($t0,$($t),*) = (iter.next().unwrap().parse::<$t0>()?,$((iter.next().unwrap().parse::<$t>()?)),*);
-                    Ok(result)
-                };
-                Some(wrap())
+macro_rules! from_capture_by_parse {
+    ($($t:ty),*) => {
+        $(impl $crate::parsing::FromCapture<'_> for $t {
+            fn from_capture(capture: Option<&str>) -> Result<Self, $crate::error::AocError> {
+                $crate::parsing::parse_capture(capture)
             }
+        })*
+    };
+}
+
+from_capture_by_parse!(
+    i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char, String
+);
+
+pub fn capture<'a, T: FromCapture<'a>>(
+    group: impl Display,
+    capture: Option<&'a str>,
+) -> Result<T, AocError> {
+    T::from_capture(capture).map_err(|e| format!("Group {group}: {e}").into())
+}
+
+// regex_parse!(REGEX, text, (i32, Option<char>, &str)) parses the groups in order into a tuple,
+// regex_parse!(REGEX, text, Valve { name: &str, rate: i32 }) parses the named groups into the
+// fields of the same name. Returns None if the regex does not match, otherwise the value or
+// an error naming the group that could not be parsed. &str outputs borrow from text.
+#[macro_export]
+macro_rules! regex_parse {
+    ($reg:expr, $text:expr, ($($t:ty),+ $(,)?)) => {
+        $reg.captures($text).map(|captures| {
+            let mut groups = captures.iter().enumerate().skip(1);
+            Ok::<_, $crate::error::AocError>(($({
+                // a missing group is an error of the regex, not of the text
+                let (index, group) = groups.next().expect("regex has fewer groups than types");
+                $crate::parsing::capture::<$t>(index, group.map(|m| m.as_str()))?
+            },)+))
+        })
+    };
+    ($reg:expr, $text:expr, $name:ident { $($field:ident: $t:ty),+ $(,)? }) => {
+        $reg.captures($text).map(|captures| {
+            Ok::<_, $crate::error::AocError>($name {
+                $($field: $crate::parsing::capture::<$t>(
+                    stringify!($field),
+                    captures.name(stringify!($field)).map(|m| m.as_str()),
+                )?),+
+            })
+        })
+    };
+}
+
+// The signed integers in a text, like 2, 18, -2 and 15 in "Sensor at x=2, y=18: closest
+// beacon is at x=-2, y=15". A minus sign right after a digit is a separator, so "2-4" is 2
+// and 4.
+pub struct Integers<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+pub fn integers<S: AsRef<[u8]> + ?Sized>(text: &S) -> Integers<'_> {
+    Integers {
+        text: text.as_ref(),
+        pos: 0,
+    }
+}
+
+impl Iterator for Integers<'_> {
+    type Item = Result<i64, AocError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.text;
+        let start = self.pos + text[self.pos..].iter().position(u8::is_ascii_digit)?;
+        let digits = text[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        self.pos = start + digits;
+        let negative = start > 0
+            && text[start - 1] == b'-'
+            && (start == 1 || !text[start - 2].is_ascii_digit());
+        let value = text[start..self.pos].iter().try_fold(0i64, |value, digit| {
+            value.checked_mul(10)?.checked_add((digit - b'0') as i64)
+        });
+        Some(match value {
+            Some(value) if negative => Ok(-value),
+            Some(value) => Ok(value),
+            None => Err(AocError::overflow(format!(
+                "{} does not fit in an i64",
+                String::from_utf8_lossy(&text[start..self.pos])
+            ))),
+        })
+    }
+}
+
+fn convert<T: TryFrom<i64>>(value: i64) -> Result<T, AocError> {
+    T::try_from(value)
+        .map_err(|_| AocError::overflow(format!("{value} does not fit in {}", type_name::<T>())))
+}
+
+pub fn ints_vec<T: TryFrom<i64>, S: AsRef<[u8]> + ?Sized>(text: &S) -> Result<Vec<T>, AocError> {
+    integers(text).map(|value| convert(value?)).collect()
+}
+
+// Splits input into the parts matching the {} placeholders of pattern, for scan!. A
+// placeholder ends where the literal text after it starts, the last one at the end of input.
+pub fn scan_pieces<'a>(
+    pattern: &str,
+    input: &'a str,
+    placeholders: usize,
+) -> Result<Vec<&'a str>, AocError> {
+    let mut literals = pattern.split("{}");
+    if pattern.matches("{}").count() != placeholders {
+        Err(format!(
+            "'{pattern}' does not have {placeholders} placeholders"
+        ))?;
+    }
+    // split always returns at least one item
+    let mut rest = input
+        .strip_prefix(literals.next().unwrap())
+        .ok_or_else(|| format!("'{input}' does not match '{pattern}'"))?;
+    let mut pieces = Vec::with_capacity(placeholders);
+    for literal in literals {
+        let end = match literal {
+            "" if pieces.len() + 1 < placeholders => Err(format!(
+                "'{pattern}' has placeholders without text between them"
+            ))?,
+            "" => rest.len(),
+            literal => rest
+                .find(literal)
+                .ok_or_else(|| format!("'{input}' does not match '{pattern}'"))?,
+        };
+        pieces.push(&rest[..end]);
+        rest = &rest[end + literal.len()..];
+    }
+    if !rest.is_empty() {
+        Err(format!("'{input}' has '{rest}' after matching '{pattern}'"))?;
+    }
+    Ok(pieces)
+}
+
+pub fn scan_value<T: FromStr>(pattern: &str, index: usize, piece: &str) -> Result<T, AocError>
+where
+    T::Err: Display,
+{
+    piece.parse().map_err(|e| {
+        format!(
+            "'{pattern}' placeholder {}: could not parse '{piece}' as {}: {e}",
+            index + 1,
+            type_name::<T>()
+        )
+        .into()
+    })
+}
+
+// scan!(line, "move {} from {} to {}", usize, usize, usize) parses every {} into the type
+// at its position and returns them as a tuple. The pattern is matched with plain string
+// searches, a placeholder extends up to the first occurrence of the text that follows it.
+#[macro_export]
+macro_rules! scan {
+    ($input:expr, $pattern:literal, $($t:ty),+ $(,)?) => {
+        (|| -> Result<($($t,)+), $crate::error::AocError> {
+            let placeholders = [$(stringify!($t)),+].len();
+            let pieces = $crate::parsing::scan_pieces($pattern, $input, placeholders)?;
+            let mut pieces = pieces.into_iter().enumerate();
+            // scan_pieces returns exactly one piece for every type
+            Ok(($({
+                let (index, piece) = pieces.next().unwrap();
+                $crate::parsing::scan_value::<$t>($pattern, index, piece)?
+            },)+))
+        })()
+    };
+}
+
+// Inputs saved on other machines can have a BOM or CRLF line endings, and editors add or
+// drop blank lines at the end. Returns the input with those removed and ending in exactly
+// one newline, together with a description of the BOM and CRLF changes. Trailing spaces
+// are kept, some inputs are padded with them.
+pub fn normalize(input: &[u8]) -> (Vec<u8>, Vec<&'static str>) {
+    let mut changes = Vec::new();
+    let input = match input.strip_prefix(b"\xef\xbb\xbf") {
+        Some(rest) => {
+            changes.push("removed a UTF-8 BOM");
+            rest
         }
+        None => input,
     };
+    let mut lines: Vec<&[u8]> = input.split(|b| *b == b'\n').collect();
+    let mut crlf = false;
+    for line in lines.iter_mut() {
+        if let Some(rest) = line.strip_suffix(b"\r") {
+            crlf = true;
+            *line = rest;
+        }
+    }
+    if crlf {
+        changes.push("converted CRLF line endings");
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    let mut normalized = Vec::with_capacity(input.len() + 1);
+    for line in lines {
+        normalized.extend_from_slice(line);
+        normalized.push(b'\n');
+    }
+    (normalized, changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_parse_reports_the_first_error() {
+        let input = "1\n2\nx\n4\ny\n6\n";
+        // from one line per chunk up to a single chunk
+        for chunk_size in 0..=input.len() {
+            match parse_lines_in_chunks::<i32>(input, chunk_size) {
+                Err(AocError::Parse { line, snippet, .. }) => {
+                    assert_eq!(
+                        (line, snippet.as_str()),
+                        (Some(3), "x"),
+                        "chunk size {chunk_size}"
+                    )
+                }
+                other => panic!("chunk size {chunk_size} gave {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_parse_keeps_the_order() {
+        let input = "1\n2\n3\n4\n5\n6";
+        for chunk_size in 0..=input.len() {
+            let values: Vec<i32> = parse_lines_in_chunks(input, chunk_size).unwrap();
+            assert_eq!(values, [1, 2, 3, 4, 5, 6], "chunk size {chunk_size}");
+        }
+    }
 }
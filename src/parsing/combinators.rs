@@ -0,0 +1,292 @@
+use itertools::Itertools;
+use std::{error::Error, fmt::Display, marker::PhantomData, str::FromStr};
+
+// Small parser combinators over &str. A parser returns what it parsed and the rest of the
+// input. Anything implementing Parser can be combined, including plain functions, so a
+// recursive grammar is just a recursive function. Parsers borrow from the input and only
+// allocate for the values they return, failures are Copy and are turned into a readable
+// ParseError once at the end by parse_all.
+pub type PResult<'a, T> = Result<(T, &'a str), Failure>;
+
+const MAX_EXPECTED: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Expected {
+    Literal(&'static str),
+    Named(&'static str),
+}
+
+impl Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expected::Literal(literal) => write!(f, "'{literal}'"),
+            Expected::Named(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+// What was expected where parsing failed. The position is stored as the length of the input
+// that was left, so it does not depend on where the parser started.
+#[derive(Clone, Copy, Debug)]
+pub struct Failure {
+    remaining: usize,
+    expected: [Expected; MAX_EXPECTED],
+    count: usize,
+}
+
+impl Failure {
+    fn expecting(rest: &str, expected: Expected) -> Failure {
+        Failure {
+            remaining: rest.len(),
+            expected: [expected; MAX_EXPECTED],
+            count: 1,
+        }
+    }
+
+    // for hand written parsers, `expected` describes what should have come at `rest`
+    pub fn new(rest: &str, expected: &'static str) -> Failure {
+        Failure::expecting(rest, Expected::Named(expected))
+    }
+
+    // the alternative that got furthest explains the failure best, alternatives that failed
+    // at the same place are all listed
+    fn merge(self, other: Failure) -> Failure {
+        match self.remaining.cmp(&other.remaining) {
+            std::cmp::Ordering::Less => self,
+            std::cmp::Ordering::Greater => other,
+            std::cmp::Ordering::Equal => {
+                let mut merged = self;
+                for &expected in &other.expected[..other.count] {
+                    if merged.count < MAX_EXPECTED && !merged.expected().contains(&expected) {
+                        merged.expected[merged.count] = expected;
+                        merged.count += 1;
+                    }
+                }
+                merged
+            }
+        }
+    }
+
+    fn expected(&self) -> &[Expected] {
+        &self.expected[..self.count]
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub input: String,
+    pub column: usize,
+    pub expected: String,
+    pub found: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.describe(), self.column)
+    }
+}
+
+impl Error for ParseError {}
+
+impl ParseError {
+    // the error without its position, for callers that show the position themselves
+    pub fn describe(&self) -> String {
+        format!("Expected {}, found {}", self.expected, self.found)
+    }
+
+    fn new(input: &str, failure: Failure) -> ParseError {
+        let rest = &input[input.len() - failure.remaining..];
+        let expected = match failure.expected() {
+            [init @ .., last] if !init.is_empty() => {
+                format!("{} or {last}", init.iter().join(", "))
+            }
+            expected => expected.iter().join(""),
+        };
+        let found = match rest.chars().take(10).collect::<String>() {
+            snippet if snippet.is_empty() => "end of input".to_string(),
+            snippet if snippet.len() < rest.len() => format!("'{snippet}...'"),
+            snippet => format!("'{snippet}'"),
+        };
+        ParseError {
+            input: input.to_string(),
+            column: input[..input.len() - failure.remaining].chars().count() + 1,
+            expected,
+            found,
+        }
+    }
+}
+
+pub trait Parser<'a> {
+    type Output;
+    fn parse(&self, input: &'a str) -> PResult<'a, Self::Output>;
+
+    fn map<F, U>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Output) -> U,
+    {
+        Map(self, f)
+    }
+
+    fn or<P>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+        P: Parser<'a, Output = Self::Output>,
+    {
+        Or(self, other)
+    }
+}
+
+impl<'a, T, F> Parser<'a> for F
+where
+    F: Fn(&'a str) -> PResult<'a, T>,
+{
+    type Output = T;
+    fn parse(&self, input: &'a str) -> PResult<'a, T> {
+        self(input)
+    }
+}
+
+// Parses all of the input, anything left over is an error
+pub fn parse_all<'a, P: Parser<'a>>(parser: P, input: &'a str) -> Result<P::Output, ParseError> {
+    match parser.parse(input) {
+        Ok((value, "")) => Ok(value),
+        Ok((_, rest)) => Err(ParseError::new(input, Failure::new(rest, "end of input"))),
+        Err(failure) => Err(ParseError::new(input, failure)),
+    }
+}
+
+pub struct Map<P, F>(P, F);
+
+impl<'a, P: Parser<'a>, F: Fn(P::Output) -> U, U> Parser<'a> for Map<P, F> {
+    type Output = U;
+    fn parse(&self, input: &'a str) -> PResult<'a, U> {
+        self.0
+            .parse(input)
+            .map(|(value, rest)| ((self.1)(value), rest))
+    }
+}
+
+// tries the second parser from the same place when the first one fails
+pub struct Or<P, Q>(P, Q);
+
+impl<'a, P: Parser<'a>, Q: Parser<'a, Output = P::Output>> Parser<'a> for Or<P, Q> {
+    type Output = P::Output;
+    fn parse(&self, input: &'a str) -> PResult<'a, P::Output> {
+        self.0
+            .parse(input)
+            .or_else(|first| self.1.parse(input).map_err(|second| first.merge(second)))
+    }
+}
+
+// a tuple of parsers parses them one after the other
+macro_rules! sequence {
+    ($($p:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<'a, $($p: Parser<'a>),*> Parser<'a> for ($($p,)*) {
+            type Output = ($($p::Output,)*);
+            fn parse(&self, input: &'a str) -> PResult<'a, Self::Output> {
+                let ($($p,)*) = self;
+                let rest = input;
+                $(let ($p, rest) = $p.parse(rest)?;)*
+                Ok((($($p,)*), rest))
+            }
+        }
+    };
+}
+
+sequence!(A, B);
+sequence!(A, B, C);
+sequence!(A, B, C, D);
+sequence!(A, B, C, D, E);
+sequence!(A, B, C, D, E, F);
+sequence!(A, B, C, D, E, F, G);
+sequence!(A, B, C, D, E, F, G, H);
+
+pub struct Literal(&'static str);
+
+pub fn lit(literal: &'static str) -> Literal {
+    Literal(literal)
+}
+
+impl<'a> Parser<'a> for Literal {
+    type Output = ();
+    fn parse(&self, input: &'a str) -> PResult<'a, ()> {
+        match input.strip_prefix(self.0) {
+            Some(rest) => Ok(((), rest)),
+            None => Err(Failure::expecting(input, Expected::Literal(self.0))),
+        }
+    }
+}
+
+pub struct Int<T>(PhantomData<T>);
+
+// an optional minus sign followed by digits, parsed into any integer type
+pub fn int<T: FromStr>() -> Int<T> {
+    Int(PhantomData)
+}
+
+impl<'a, T: FromStr> Parser<'a> for Int<T> {
+    type Output = T;
+    fn parse(&self, input: &'a str) -> PResult<'a, T> {
+        let sign = usize::from(input.starts_with('-'));
+        let digits = input[sign..].bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 {
+            return Err(Failure::new(input, "integer"));
+        }
+        let (number, rest) = input.split_at(sign + digits);
+        match number.parse() {
+            Ok(value) => Ok((value, rest)),
+            Err(_) => Err(Failure::new(input, "integer in range")),
+        }
+    }
+}
+
+pub struct TakeWhile1 {
+    what: &'static str,
+    pred: fn(char) -> bool,
+}
+
+// the longest non-empty prefix of characters matching pred, described as `what` in errors
+pub fn take_while1(what: &'static str, pred: fn(char) -> bool) -> TakeWhile1 {
+    TakeWhile1 { what, pred }
+}
+
+impl<'a> Parser<'a> for TakeWhile1 {
+    type Output = &'a str;
+    fn parse(&self, input: &'a str) -> PResult<'a, &'a str> {
+        let end = input.find(|c| !(self.pred)(c)).unwrap_or(input.len());
+        if end == 0 {
+            return Err(Failure::new(input, self.what));
+        }
+        Ok(input.split_at(end))
+    }
+}
+
+pub struct Separated<P, S>(P, S);
+
+// zero or more items with a separator between them. Once a separator has been parsed an item
+// must follow, so a list like "1,2," fails at the end instead of stopping before the comma.
+pub fn separated<'a, P: Parser<'a>, S: Parser<'a>>(item: P, separator: S) -> Separated<P, S> {
+    Separated(item, separator)
+}
+
+impl<'a, P: Parser<'a>, S: Parser<'a>> Parser<'a> for Separated<P, S> {
+    type Output = Vec<P::Output>;
+    fn parse(&self, input: &'a str) -> PResult<'a, Vec<P::Output>> {
+        let mut items = Vec::new();
+        let (first, mut rest) = match self.0.parse(input) {
+            Ok(parsed) => parsed,
+            // an item that failed after consuming input was there but broken
+            Err(failure) if failure.remaining < input.len() => return Err(failure),
+            Err(_) => return Ok((items, input)),
+        };
+        items.push(first);
+        while let Ok((_, after_separator)) = self.1.parse(rest) {
+            let (item, after_item) = self.0.parse(after_separator)?;
+            items.push(item);
+            rest = after_item;
+        }
+        Ok((items, rest))
+    }
+}
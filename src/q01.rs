@@ -1,44 +1,95 @@
 use itertools::Itertools;
 use std::cmp::Reverse;
-use std::{error::Error, io::BufRead};
+use std::{
+    io::{BufRead, Write},
+    num::ParseIntError,
+    str::FromStr,
+};
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    parsing::{input_str, sections, write_lines, write_sections, ToBufWrite},
+    random::Rng,
+};
 
-struct Elf(i32);
+struct Elf(Vec<i32>);
 
 impl Elf {
     fn total_calories(&self) -> i32 {
-        self.0
+        self.0.iter().sum()
     }
 }
 
-impl FromBufRead for Elf {
-    type Error = Box<dyn Error>;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let calories: Result<i32, _> = br
-            .lines()
-            .map_while(|line| -> Option<Result<i32, _>> {
-                match line {
-                    Err(_) => None,
-                    Ok(l) if l.is_empty() => None,
-                    Ok(l) => Some(l.parse()),
-                }
-            })
-            .sum();
-        Ok(Elf(calories?))
+impl FromStr for Elf {
+    type Err = ParseIntError;
+    fn from_str(section: &str) -> Result<Self, Self::Err> {
+        Ok(Elf(section.lines().map(str::parse).try_collect()?))
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
-    let max_calories =
-        Elf::read_iter(&mut buf).fold_ok(0, |f, elf| std::cmp::max(f, elf.total_calories()))?;
+impl ToBufWrite for Elf {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        write_lines(&self.0, bw)
+    }
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let elves: Vec<Elf> = sections(input_str(input)?)
+        .map(Elf::from_str)
+        .try_collect()?;
+    let mut out = Vec::new();
+    write_sections(&elves, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i32, AocError> {
+    let max_calories = sections(input_str(input)?)
+        .map(Elf::from_str)
+        .fold_ok(0, |f, elf| std::cmp::max(f, elf.total_calories()))?;
     Ok(max_calories)
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
-    let mut calories: Vec<_> = Elf::read_iter(&mut buf)
-        .map_ok(|elf| elf.total_calories())
+pub fn b(input: &[u8]) -> Result<i32, AocError> {
+    let mut calories: Vec<_> = sections(input_str(input)?)
+        .map(|section| Elf::from_str(section).map(|elf| elf.total_calories()))
         .try_collect()?;
     calories.select_nth_unstable_by_key(2, |e| Reverse(*e));
     Ok(calories.iter().take(3).sum())
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // b needs at least three elves
+    for elf in 0..size.max(3) {
+        if elf > 0 {
+            writeln!(out)?;
+        }
+        for _ in 0..rng.range(1..=15) {
+            writeln!(out, "{}", rng.range(1000..=60000))?;
+        }
+    }
+    Ok(())
+}
+
+fn reference_calories(mut buf: impl BufRead) -> Result<Vec<i32>, AocError> {
+    let mut input = String::new();
+    buf.read_to_string(&mut input)?;
+    let mut calories = Vec::new();
+    for elf in input.split("\n\n") {
+        let mut total = 0;
+        for line in elf.lines() {
+            total += line.parse::<i32>()?;
+        }
+        calories.push(total);
+    }
+    Ok(calories)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i32, AocError> {
+    Ok(reference_calories(buf)?.into_iter().max().unwrap_or(0))
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i32, AocError> {
+    let mut calories = reference_calories(buf)?;
+    calories.sort();
+    Ok(calories.iter().rev().take(3).sum())
+}
@@ -1,9 +1,20 @@
-use std::{error::Error, io::BufRead, str::FromStr};
+use itertools::Itertools;
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
 use lazy_static::lazy_static;
 use regex::Regex;
 
-use crate::{parsing::parse_by_line, regex_parse};
+use crate::{
+    error::AocError,
+    from_capture_by_parse,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+    regex_parse,
+};
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 #[repr(i32)]
@@ -32,6 +43,18 @@ impl FromStr for Rps {
     }
 }
 
+impl Display for Rps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Rps::*;
+        let letter = match self {
+            Rock => 'A',
+            Paper => 'B',
+            Scissors => 'C',
+        };
+        write!(f, "{letter}")
+    }
+}
+
 #[derive(Copy, Clone)]
 #[repr(i32)]
 enum GameResult {
@@ -46,7 +69,9 @@ impl GameResult {
     }
 }
 
-fn partial_parse(line: &str) -> Result<(Rps, char), Box<dyn Error>> {
+from_capture_by_parse!(Rps);
+
+fn partial_parse(line: &str) -> Result<(Rps, char), AocError> {
     lazy_static! {
         static ref REGEX: Regex = Regex::new(r"^([ABC]) ([XYZ])$").unwrap();
     }
@@ -72,7 +97,7 @@ impl Game {
 }
 
 impl FromStr for Game {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(line: &str) -> Result<Self, Self::Err> {
         let (player1, p2) = partial_parse(line)?;
         use Rps::*;
@@ -86,10 +111,37 @@ impl FromStr for Game {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
+impl Display for Game {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Rps::*;
+        let player2 = match self.player2 {
+            Rock => 'X',
+            Paper => 'Y',
+            Scissors => 'Z',
+        };
+        write!(f, "{} {player2}", self.player1)
+    }
+}
+
+// a and b read the same lines in different ways, which have to agree on how to write them
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let input = input_str(input)?;
+    let games: Vec<Game> = parse_lines(input).try_collect()?;
+    let expected_games: Vec<ExpectedGame> = parse_lines(input).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&games, &mut out)?;
+    let mut expected_out = Vec::new();
+    write_lines(&expected_games, &mut expected_out)?;
+    if out != expected_out {
+        Err("Game and ExpectedGame write different lines")?;
+    }
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i32, AocError> {
     let mut total = 0;
-    for game in parse_by_line::<Game>(buf) {
-        let game = game??;
+    for game in parse_lines::<Game>(input_str(input)?) {
+        let game = game?;
         total += game.result().score() + game.player2.score();
     }
     Ok(total)
@@ -121,7 +173,7 @@ impl ExpectedGame {
 }
 
 impl FromStr for ExpectedGame {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(line: &str) -> Result<Self, Self::Err> {
         let (player1, res) = partial_parse(line)?;
         use GameResult::*;
@@ -135,11 +187,72 @@ impl FromStr for ExpectedGame {
     }
 }
 
-pub fn b(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
+impl Display for ExpectedGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use GameResult::*;
+        let result = match self.result {
+            Player1Win => 'X',
+            Draw => 'Y',
+            Player2Win => 'Z',
+        };
+        write!(f, "{} {result}", self.player1)
+    }
+}
+
+pub fn b(input: &[u8]) -> Result<i32, AocError> {
     let mut total = 0;
-    for game in parse_by_line::<ExpectedGame>(buf) {
-        let game = game??;
+    for game in parse_lines::<ExpectedGame>(input_str(input)?) {
+        let game = game?;
         total += game.result.score() + game.player2().score();
     }
     Ok(total)
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    for _ in 0..size {
+        let player1 = rng.choose(&['A', 'B', 'C']);
+        let player2 = rng.choose(&['X', 'Y', 'Z']);
+        writeln!(out, "{player1} {player2}")?;
+    }
+    Ok(())
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i32, AocError> {
+    let mut total = 0;
+    for line in buf.lines() {
+        // shape score plus 0 for a loss, 3 for a draw and 6 for a win
+        total += match line?.as_str() {
+            "A X" => 1 + 3,
+            "A Y" => 2 + 6,
+            "A Z" => 3,
+            "B X" => 1,
+            "B Y" => 2 + 3,
+            "B Z" => 3 + 6,
+            "C X" => 1 + 6,
+            "C Y" => 2,
+            "C Z" => 3 + 3,
+            l => Err(format!("Unexpected line {l}"))?,
+        };
+    }
+    Ok(total)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i32, AocError> {
+    let mut total = 0;
+    for line in buf.lines() {
+        // X means lose, Y draw and Z win
+        total += match line?.as_str() {
+            "A X" => 3,
+            "A Y" => 1 + 3,
+            "A Z" => 2 + 6,
+            "B X" => 1,
+            "B Y" => 2 + 3,
+            "B Z" => 3 + 6,
+            "C X" => 2,
+            "C Y" => 3 + 3,
+            "C Z" => 1 + 6,
+            l => Err(format!("Unexpected line {l}"))?,
+        };
+    }
+    Ok(total)
+}
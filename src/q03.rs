@@ -1,7 +1,15 @@
 use itertools::Itertools;
-use std::{error::Error, io::BufRead, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
-use crate::parsing::parse_by_line;
+use crate::{
+    error::AocError,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+};
 
 // might be faster in a bitset, but performance is not an issue (yet)
 struct Items(u64);
@@ -35,12 +43,63 @@ impl FromStr for Items {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
+impl Items {
+    fn letters(&self) -> impl Iterator<Item = char> + '_ {
+        (b'a'..=b'z')
+            .chain(b'A'..=b'Z')
+            .enumerate()
+            .filter(|(index, _)| self.0 & (1 << (index + 1)) != 0)
+            .map(|(_, letter)| letter as char)
+    }
+}
+
+impl Display for Items {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.letters().try_for_each(|letter| write!(f, "{letter}"))
+    }
+}
+
+// a line for a, split into its two compartments of the same size
+struct Rucksack(Items, Items);
+
+impl FromStr for Rucksack {
+    type Err = &'static str;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (comp1, comp2) = line
+            .split_at_checked(line.len() / 2)
+            .ok_or("Unexpected item")?;
+        Ok(Rucksack(Items::from_str(comp1)?, Items::from_str(comp2)?))
+    }
+}
+
+impl Display for Rucksack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the compartments are written with every item once, the one with fewer items repeats
+        // its first item to keep them the same size
+        let size = self.0.letters().count().max(self.1.letters().count());
+        for items in [&self.0, &self.1] {
+            let repeated = items.letters().next().into_iter().cycle();
+            for letter in items.letters().chain(repeated).take(size) {
+                write!(f, "{letter}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    // b reads the same lines as Items, which accepts exactly the lines a Rucksack does
+    let rucksacks: Vec<Rucksack> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&rucksacks, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i32, AocError> {
     let mut total_prio = 0;
-    for line in buf.lines() {
-        let line = line?;
-        let (comp1, comp2) = line.split_at(line.len() / 2);
-        let common = Items::from_str(comp1)?.common(&Items::from_str(comp2)?);
+    for rucksack in parse_lines::<Rucksack>(input_str(input)?) {
+        let Rucksack(comp1, comp2) = rucksack?;
+        let common = comp1.common(&comp2);
         total_prio += common
             .try_to_single_prio()
             .ok_or("Expected one common item")?;
@@ -48,14 +107,82 @@ pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
     Ok(total_prio)
 }
 
-pub fn b(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
+pub fn b(input: &[u8]) -> Result<i32, AocError> {
     let mut sum = 0;
-    for (e1, e2, e3) in parse_by_line::<Items>(buf).tuples() {
-        sum += e1??
-            .common(&e2??)
-            .common(&e3??)
+    for (e1, e2, e3) in parse_lines::<Items>(input_str(input)?).tuples() {
+        sum += e1?
+            .common(&e2?)
+            .common(&e3?)
             .try_to_single_prio()
             .ok_or("No single common item")?;
     }
     Ok(sum)
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    let mut items: Vec<u8> = (b'a'..=b'z').chain(b'A'..=b'Z').collect();
+    // b needs groups of three elves
+    for _ in 0..size.max(1).div_ceil(3) {
+        // every elf in the group gets its own third of the items, so only the badge is shared
+        rng.shuffle(&mut items);
+        let (badge, others) = items.split_first().unwrap(); // cannot fail, there are 52 items
+        for own_items in others.chunks(others.len() / 3) {
+            let mut own_items = own_items.to_vec();
+            rng.shuffle(&mut own_items);
+            let (left_only, right_only) = own_items.split_at(own_items.len() / 2);
+            let common = *rng.choose(&[*badge, left_only[0], right_only[0]]);
+            let half_size = rng.range(2..=16) as usize;
+            let mut left = vec![common, *badge];
+            let mut right = vec![common];
+            while left.len() < half_size {
+                left.push(*rng.choose(left_only));
+            }
+            while right.len() < left.len() {
+                right.push(*rng.choose(right_only));
+            }
+            rng.shuffle(&mut left);
+            rng.shuffle(&mut right);
+            left.extend(right);
+            out.write_all(&left)?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+fn reference_priority(common: Vec<char>) -> Result<i32, AocError> {
+    match common[..] {
+        [c @ 'a'..='z'] => Ok(c as i32 - 'a' as i32 + 1),
+        [c @ 'A'..='Z'] => Ok(c as i32 - 'A' as i32 + 27),
+        _ => Err("Expected one common item".into()),
+    }
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i32, AocError> {
+    let mut total = 0;
+    for line in buf.lines() {
+        let line = line?;
+        let (left, right) = line.split_at(line.len() / 2);
+        total += reference_priority(
+            left.chars()
+                .filter(|c| right.contains(*c))
+                .unique()
+                .collect(),
+        )?;
+    }
+    Ok(total)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i32, AocError> {
+    let lines: Vec<String> = buf.lines().try_collect()?;
+    let mut total = 0;
+    for group in lines.chunks_exact(3) {
+        let common = group[0]
+            .chars()
+            .filter(|c| group[1].contains(*c) && group[2].contains(*c))
+            .unique()
+            .collect();
+        total += reference_priority(common)?;
+    }
+    Ok(total)
+}
@@ -1,8 +1,19 @@
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::{error::Error, io::BufRead, ops::RangeInclusive, str::FromStr};
+use itertools::Itertools;
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
-use crate::{parsing::parse_by_line, regex_parse};
+use crate::{
+    error::AocError,
+    parsing::{
+        combinators::{int, lit, parse_all, Parser},
+        input_str, parse_lines, write_lines,
+    },
+    random::Rng,
+};
 
 struct RangePair<T> {
     left: RangeInclusive<T>,
@@ -23,38 +34,91 @@ impl<T: PartialOrd> RangePair<T> {
 
 impl FromStr for RangePair<i32> {
     // also works for other integers, but no trait to restrict that
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref REGEX: Regex = Regex::new(r"^(\d+)-(\d+),(\d+)-(\d+)$").unwrap();
-        }
-        match regex_parse!(REGEX, s, (i32, i32, i32, i32)) {
-            None => Err("Range pair did not match".into()),
-            Some(Err(e)) => Err(e),
-            Some(Ok((s1, e1, s2, e2))) => Ok(RangePair {
-                left: RangeInclusive::new(s1, e1),
-                right: RangeInclusive::new(s2, e2),
-            }),
+        let range = || (int(), lit("-"), int()).map(|(start, _, end)| start..=end);
+        let (left, _, right) = parse_all((range(), lit(","), range()), s)?;
+        Ok(RangePair { left, right })
+    }
+}
+
+impl<T: Display> Display for RangePair<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (left, right) = (&self.left, &self.right);
+        write!(
+            f,
+            "{}-{},{}-{}",
+            left.start(),
+            left.end(),
+            right.start(),
+            right.end()
+        )
+    }
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let pairs: Vec<RangePair<i32>> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&pairs, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let mut count = 0;
+    for pair in parse_lines::<RangePair<i32>>(input_str(input)?) {
+        if pair?.one_included_in_other() {
+            count += 1;
         }
     }
+    Ok(count)
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn b(input: &[u8]) -> Result<i32, AocError> {
     let mut count = 0;
-    for pair in parse_by_line::<RangePair<i32>>(buf) {
-        if pair??.one_included_in_other() {
+    for pair in parse_lines::<RangePair<i32>>(input_str(input)?) {
+        if pair?.has_overlap() {
             count += 1;
         }
     }
     Ok(count)
 }
 
-pub fn b(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    let mut range = || {
+        let start = rng.range(1..=99);
+        (start, rng.range(start..=99))
+    };
+    for _ in 0..size {
+        let (s1, e1) = range();
+        let (s2, e2) = range();
+        writeln!(out, "{s1}-{e1},{s2}-{e2}")?;
+    }
+    Ok(())
+}
+
+fn reference_count(
+    buf: impl BufRead,
+    counts: impl Fn(RangeInclusive<i32>, RangeInclusive<i32>) -> bool,
+) -> Result<usize, AocError> {
     let mut count = 0;
-    for pair in parse_by_line::<RangePair<i32>>(buf) {
-        if pair??.has_overlap() {
+    for line in buf.lines() {
+        let line = line?;
+        let (left, right) = line.split_once(',').ok_or("Expected ,")?;
+        let (s1, e1) = left.split_once('-').ok_or("Expected -")?;
+        let (s2, e2) = right.split_once('-').ok_or("Expected -")?;
+        if counts(s1.parse()?..=e1.parse()?, s2.parse()?..=e2.parse()?) {
             count += 1;
         }
     }
     Ok(count)
 }
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_count(buf, |left, right| {
+        left.clone().all(|s| right.contains(&s)) || right.clone().all(|s| left.contains(&s))
+    })
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_count(buf, |left, right| left.clone().any(|s| right.contains(&s)))
+}
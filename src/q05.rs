@@ -1,10 +1,18 @@
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::{error::Error, io::BufRead, mem::take, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    mem::take,
+    str::FromStr,
+};
 
 use crate::{
-    parsing::{parse_by_line, FromBufRead},
-    regex_parse,
+    error::AocError,
+    parsing::{ints_vec, read_all, FromBufRead, Line, Section, ToBufWrite},
+    random::Rng,
+    scan,
 };
 
 struct Instruction {
@@ -14,55 +22,68 @@ struct Instruction {
 }
 
 impl FromStr for Instruction {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref REGEX: Regex = Regex::new(r"^move (\d+) from (\d+) to (\d+)$").unwrap();
-        }
-        match regex_parse!(REGEX, s, (usize, usize, usize)) {
-            None => Err("Instruction could not be parsed".into()),
-            Some(Err(e)) => Err(e),
-            Some(Ok((amount, from, to))) => Ok(Instruction { amount, from, to }),
-        }
+        let (amount, from, to) = scan!(s, "move {} from {} to {}", usize, usize, usize)?;
+        Ok(Instruction { amount, from, to })
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
     }
 }
 
 struct RowOfStacksOfCrates(Vec<Vec<char>>);
 
 impl FromBufRead for RowOfStacksOfCrates {
-    type Error = Box<dyn Error>;
+    type Error = AocError;
     fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let mut row_of_stacks = Vec::<Vec<char>>::new();
-        for line in br.lines() {
-            let line = line?;
-            if line.starts_with(" 1   2") {
-                break;
-            }
+        let lines = Vec::<Line<String>>::read(br)?;
+        let (numbers, drawing) = lines.split_last().ok_or("Expected stack numbers")?;
+        let numbers = ints_vec::<usize, _>(&numbers.0)?;
+        if numbers.is_empty() || !numbers.iter().copied().eq(1..=numbers.len()) {
+            Err("Expected stack numbers 1, 2, 3 and so on")?;
+        }
+        let mut row_of_stacks = vec![Vec::new(); numbers.len()];
+        for line in drawing.iter().rev() {
             lazy_static! {
                 static ref REGEX: Regex = Regex::new(r"\[([A-Z])\]").unwrap();
             }
-            for cap in REGEX.captures_iter(&line) {
+            for cap in REGEX.captures_iter(&line.0) {
                 let m = cap.get(1).unwrap();
                 let stack = m.start() / 4;
                 let c = m.as_str().chars().next().unwrap();
-                if stack >= row_of_stacks.len() {
-                    row_of_stacks.resize(stack + 1, Default::default());
-                }
-                row_of_stacks[stack].push(c);
+                row_of_stacks
+                    .get_mut(stack)
+                    .ok_or("Crate is not above a numbered stack")?
+                    .push(c);
             }
         }
-        for stack in row_of_stacks.iter_mut() {
-            stack.reverse();
-        }
-        match br.lines().next() {
-            None => return Err("Expected empty line".into()),
-            Some(Err(e)) => return Err(e.into()),
-            Some(_) => {}
-        };
         Ok(RowOfStacksOfCrates(row_of_stacks))
     }
 }
 
+impl ToBufWrite for RowOfStacksOfCrates {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        let height = self.0.iter().map(Vec::len).max().unwrap_or(0);
+        for level in (0..height).rev() {
+            let row = self
+                .0
+                .iter()
+                .map(|stack| match stack.get(level) {
+                    Some(c) => format!("[{c}]"),
+                    None => "   ".to_string(),
+                })
+                .join(" ");
+            writeln!(bw, "{row}")?;
+        }
+        let numbers = (1..=self.0.len()).map(|n| format!(" {n} ")).join(" ");
+        writeln!(bw, "{numbers}")
+    }
+}
+
 impl RowOfStacksOfCrates {
     fn move_crates_one_by_one(&mut self, instruction: &Instruction) -> Result<(), &'static str> {
         for _ in 0..instruction.amount {
@@ -88,18 +109,123 @@ impl RowOfStacksOfCrates {
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<String, Box<dyn Error>> {
-    let mut row = RowOfStacksOfCrates::read(&mut buf)?;
-    for instruction in parse_by_line::<Instruction>(buf) {
-        row.move_crates_one_by_one(&instruction??)?;
+type Input = (Section<RowOfStacksOfCrates>, Vec<Line<Instruction>>);
+
+pub fn reformat(mut input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let input = read_all::<Input>(&mut input)?;
+    let mut out = Vec::new();
+    input.write(&mut out)?;
+    Ok(out)
+}
+
+pub fn a(mut input: &[u8]) -> Result<String, AocError> {
+    let (Section(mut row), instructions) = read_all::<Input>(&mut input)?;
+    for Line(instruction) in instructions {
+        row.move_crates_one_by_one(&instruction)?;
     }
     Ok(row.top_crates())
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<String, Box<dyn Error>> {
-    let mut row = RowOfStacksOfCrates::read(&mut buf)?;
-    for instruction in parse_by_line::<Instruction>(buf) {
-        row.move_crate_group(&instruction??);
+pub fn b(mut input: &[u8]) -> Result<String, AocError> {
+    let (Section(mut row), instructions) = read_all::<Input>(&mut input)?;
+    for Line(instruction) in instructions {
+        row.move_crate_group(&instruction);
     }
     Ok(row.top_crates())
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // the header is only recognized with single digit stack numbers
+    const NUM_STACKS: usize = 9;
+    // like in the real input every stack starts with a crate
+    let mut heights: Vec<usize> = (0..NUM_STACKS).map(|_| 1 + rng.below(8)).collect();
+    for level in (0..*heights.iter().max().unwrap()).rev() {
+        let row = heights
+            .iter()
+            .map(|height| {
+                if *height > level {
+                    format!("[{}]", (b'A' + rng.below(26) as u8) as char)
+                } else {
+                    "   ".to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(out, "{row}")?;
+    }
+    let numbers = (1..=NUM_STACKS)
+        .map(|n| format!(" {n} "))
+        .collect::<Vec<_>>();
+    writeln!(out, "{}", numbers.join(" "))?;
+    writeln!(out)?;
+    // simulate the heights so that every move only takes crates that are there
+    for _ in 0..size {
+        let from = loop {
+            let from = rng.below(NUM_STACKS);
+            if heights[from] > 0 {
+                break from;
+            }
+        };
+        let to = (from + 1 + rng.below(NUM_STACKS - 1)) % NUM_STACKS;
+        let amount = 1 + rng.below(heights[from]);
+        heights[from] -= amount;
+        heights[to] += amount;
+        writeln!(out, "move {amount} from {} to {}", from + 1, to + 1)?;
+    }
+    Ok(())
+}
+
+fn reference_crane(buf: impl BufRead, one_by_one: bool) -> Result<String, AocError> {
+    let lines = buf.lines().collect::<Result<Vec<String>, _>>()?;
+    let blank = lines
+        .iter()
+        .position(|l| l.is_empty())
+        .ok_or("Expected empty line")?;
+    let (drawing, moves) = lines.split_at(blank);
+    let (numbers, crates) = drawing.split_last().ok_or("Expected stack numbers")?;
+    // the crate letters are in every fourth column, starting at column 1
+    let mut stacks = vec![Vec::new(); numbers.split_whitespace().count()];
+    for line in crates.iter().rev() {
+        for (i, stack) in stacks.iter_mut().enumerate() {
+            match line.as_bytes().get(1 + 4 * i) {
+                None | Some(b' ') => {}
+                Some(c) => stack.push(*c as char),
+            }
+        }
+    }
+    for line in moves.iter().skip(1) {
+        let words: Vec<&str> = line.split(' ').collect();
+        let ["move", amount, "from", from, "to", to] = words[..] else {
+            Err(format!("Could not parse move {line}"))?
+        };
+        let (amount, from, to): (usize, usize, usize) =
+            (amount.parse()?, from.parse()?, to.parse()?);
+        let from = stacks
+            .get_mut(from.wrapping_sub(1))
+            .ok_or("Unknown stack")?;
+        let remaining = from
+            .len()
+            .checked_sub(amount)
+            .ok_or("Not enough crates on stack")?;
+        let mut moved = from.split_off(remaining);
+        if one_by_one {
+            moved.reverse();
+        }
+        stacks
+            .get_mut(to.wrapping_sub(1))
+            .ok_or("Unknown stack")?
+            .extend(moved);
+    }
+    Ok(stacks
+        .iter()
+        .map(|stack| stack.last().copied().unwrap_or(' '))
+        .collect())
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<String, AocError> {
+    reference_crane(buf, true)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<String, AocError> {
+    reference_crane(buf, false)
+}
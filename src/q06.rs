@@ -1,5 +1,10 @@
 use itertools::Itertools;
-use std::{collections::VecDeque, error::Error, io::BufRead};
+use std::{
+    collections::{HashSet, VecDeque},
+    io::{BufRead, Write},
+};
+
+use crate::{error::AocError, random::Rng};
 
 struct RingBufferWithSort<T: Ord + Clone> {
     queue: VecDeque<T>,
@@ -23,10 +28,9 @@ impl<T: Ord + Clone> RingBufferWithSort<T> {
         self.sorted.insert(new_pos, value);
     }
     fn pop_front(&mut self) -> Option<T> {
-        self.queue.pop_front().map(|old| {
-            let old_pos = self.sorted.binary_search(&old).unwrap(); // unwrap is guaranteed to not fail because it was in the queue
+        self.queue.pop_front().inspect(|old| {
+            let old_pos = self.sorted.binary_search(old).unwrap(); // unwrap is guaranteed to not fail because it was in the queue
             self.sorted.remove(old_pos);
-            old
         })
     }
 }
@@ -37,32 +41,60 @@ impl<T: Ord + Clone + Eq> RingBufferWithSort<T> {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
     const NUMBYTES: usize = 4;
     let mut queue = RingBufferWithSort::with_capacity(NUMBYTES + 1);
-    for (i, c) in buf.bytes().enumerate() {
-        queue.push_back(c?);
+    for (i, c) in input.iter().enumerate() {
+        queue.push_back(*c);
         if queue.len() > NUMBYTES {
             queue.pop_front();
-            if !queue.has_duplicates() {
-                return Ok(i + 1);
-            }
+        }
+        if queue.len() == NUMBYTES && !queue.has_duplicates() {
+            return Ok(i + 1);
         }
     }
-    Err("Start of packet not found".into())
+    Err(AocError::no_solution("start of packet not found"))
 }
 
-pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
     const NUMBYTES: usize = 14;
     let mut queue = RingBufferWithSort::with_capacity(NUMBYTES + 1);
-    for (i, c) in buf.bytes().enumerate() {
-        queue.push_back(c?);
+    for (i, c) in input.iter().enumerate() {
+        queue.push_back(*c);
         if queue.len() > NUMBYTES {
             queue.pop_front();
-            if !queue.has_duplicates() {
-                return Ok(i + 1);
-            }
+        }
+        if queue.len() == NUMBYTES && !queue.has_duplicates() {
+            return Ok(i + 1);
         }
     }
-    Err("Start of message not found".into())
+    Err(AocError::no_solution("start of message not found"))
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // a small alphabet makes the markers rare, the stream ends with a guaranteed message marker
+    let mut letters: Vec<u8> = (b'a'..=b'z').collect();
+    let stream: Vec<u8> = (0..size).map(|_| letters[rng.below(5)]).collect();
+    out.write_all(&stream)?;
+    rng.shuffle(&mut letters);
+    out.write_all(&letters[..14])?;
+    writeln!(out)
+}
+
+fn reference_marker(buf: impl BufRead, length: usize) -> Result<usize, AocError> {
+    let stream = buf.bytes().collect::<Result<Vec<u8>, _>>()?;
+    for (i, window) in stream.trim_ascii_end().windows(length).enumerate() {
+        if window.iter().collect::<HashSet<_>>().len() == length {
+            return Ok(i + length);
+        }
+    }
+    Err(AocError::no_solution("marker not found"))
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_marker(buf, 4)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_marker(buf, 14)
 }
@@ -1,4 +1,16 @@
-use std::{collections::HashMap, error::Error, io::BufRead};
+use itertools::Itertools;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
+
+use crate::{
+    error::AocError,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+};
 
 struct Directory {
     subs: HashMap<String, Directory>,
@@ -12,8 +24,11 @@ impl Directory {
             recursive_size: 0,
         }
     }
-    fn add_file(&mut self, path: &[String], size: usize) -> Result<(), &'static str> {
-        self.recursive_size += size;
+    fn add_file(&mut self, path: &[String], size: usize) -> Result<(), AocError> {
+        self.recursive_size = self
+            .recursive_size
+            .checked_add(size)
+            .ok_or_else(|| AocError::overflow("the size of a directory"))?;
         if path.is_empty() {
             Ok(())
         } else {
@@ -32,43 +47,211 @@ impl Directory {
     }
 }
 
-fn build_tree(buf: impl BufRead) -> Result<Directory, Box<dyn Error>> {
-    // TODO lookup by full path is slow
-    let mut root = Directory::new();
-    let mut path = Vec::new();
-    for line in buf.lines() {
-        let line = line?;
-        if line == "$ cd /" {
-            path.clear();
+// a command or a line of what ls printed
+enum TerminalLine {
+    CdRoot,
+    CdUp,
+    Cd(String),
+    Ls,
+    Dir(String),
+    File(usize, String),
+}
+
+impl FromStr for TerminalLine {
+    type Err = AocError;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        use TerminalLine::*;
+        Ok(if line == "$ cd /" {
+            CdRoot
         } else if line == "$ cd .." {
-            path.pop().ok_or("Cannot go up from root")?;
+            CdUp
         } else if line == "$ ls" {
-            // ignore
+            Ls
         } else if let Some(l) = line.strip_prefix("$ cd ") {
-            path.push(l.to_string());
-        } else if let Some((size, _name)) = line.split_once(' ') {
-            if size != "dir" {
-                root.add_file(&path, size.parse()?)?;
-            }
+            Cd(l.to_string())
+        } else if let Some(name) = line.strip_prefix("dir ") {
+            Dir(name.to_string())
+        } else if let Some((size, name)) = line.split_once(' ') {
+            File(size.parse()?, name.to_string())
         } else {
             return Err(format!("Could not parse line {line}").into());
+        })
+    }
+}
+
+impl Display for TerminalLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use TerminalLine::*;
+        match self {
+            CdRoot => write!(f, "$ cd /"),
+            CdUp => write!(f, "$ cd .."),
+            Cd(dir) => write!(f, "$ cd {dir}"),
+            Ls => write!(f, "$ ls"),
+            Dir(name) => write!(f, "dir {name}"),
+            File(size, name) => write!(f, "{size} {name}"),
+        }
+    }
+}
+
+fn build_tree(lines: &[TerminalLine]) -> Result<Directory, AocError> {
+    // TODO lookup by full path is slow
+    let mut root = Directory::new();
+    let mut path = Vec::new();
+    for line in lines {
+        match line {
+            TerminalLine::CdRoot => path.clear(),
+            TerminalLine::CdUp => {
+                path.pop().ok_or("Cannot go up from root")?;
+            }
+            TerminalLine::Cd(dir) => path.push(dir.clone()),
+            TerminalLine::Ls | TerminalLine::Dir(_) => {}
+            TerminalLine::File(size, _name) => root.add_file(&path, *size)?,
         }
     }
     Ok(root)
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let root = build_tree(buf)?;
+fn read_tree(input: &[u8]) -> Result<Directory, AocError> {
+    let lines: Vec<TerminalLine> = parse_lines(input_str(input)?).try_collect()?;
+    build_tree(&lines)
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let lines: Vec<TerminalLine> = parse_lines(input_str(input)?).try_collect()?;
+    build_tree(&lines)?;
+    let mut out = Vec::new();
+    write_lines(&lines, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let root = read_tree(input)?;
     Ok(root.dir_sizes().iter().filter(|s| **s < 100000).sum())
 }
 
-pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let root = build_tree(buf)?;
-    let to_remove = root.recursive_size - 40000000;
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
+    let root = read_tree(input)?;
+    let to_remove = root
+        .recursive_size
+        .checked_sub(40000000)
+        .ok_or_else(|| AocError::no_solution("enough space is free already"))?;
     root.dir_sizes()
         .iter()
         .filter(|s| **s > to_remove)
         .copied()
         .min()
-        .ok_or_else(|| "No directory found".into())
+        .ok_or_else(|| AocError::no_solution("no directory is large enough"))
+}
+
+struct GeneratedDirectory {
+    name: String,
+    files: Vec<(usize, String)>,
+    subs: Vec<usize>,
+}
+
+fn write_generated(
+    dirs: &[GeneratedDirectory],
+    index: usize,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(out, "$ ls")?;
+    for sub in dirs[index].subs.iter() {
+        writeln!(out, "dir {}", dirs[*sub].name)?;
+    }
+    for (size, name) in dirs[index].files.iter() {
+        writeln!(out, "{size} {name}")?;
+    }
+    for sub in dirs[index].subs.iter() {
+        writeln!(out, "$ cd {}", dirs[*sub].name)?;
+        write_generated(dirs, *sub, out)?;
+        writeln!(out, "$ cd ..")?;
+    }
+    Ok(())
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    let name = |rng: &mut Rng| -> String {
+        (0..rng.range(1..=8))
+            .map(|_| (b'a' + rng.below(26) as u8) as char)
+            .collect()
+    };
+    let mut dirs = vec![GeneratedDirectory {
+        name: "/".into(),
+        files: Vec::new(),
+        subs: Vec::new(),
+    }];
+    for index in 1..size.max(1) {
+        let parent = rng.below(index);
+        // the index keeps names unique within a directory
+        let name = format!("{}{index}", name(rng));
+        dirs[parent].subs.push(index);
+        dirs.push(GeneratedDirectory {
+            name,
+            files: Vec::new(),
+            subs: Vec::new(),
+        });
+    }
+    // b needs more than 40000000 in use, spread about 50000000 over all directories
+    let file_size = 2 * 50_000_000 / (5 * dirs.len());
+    let mut total = 0;
+    for dir in dirs.iter_mut() {
+        for file in 0..rng.below(10) {
+            let size = 1 + rng.below(file_size);
+            total += size;
+            dir.files
+                .push((size, format!("{}{file}.{}", name(rng), name(rng))));
+        }
+    }
+    if total <= 40_000_000 {
+        dirs[0].files.push((50_000_000 - total, "padding".into()));
+    }
+    writeln!(out, "$ cd /")?;
+    write_generated(&dirs, 0, out)
+}
+
+// the total size of every directory that contains a file, by full path
+fn reference_sizes(buf: impl BufRead) -> Result<HashMap<String, usize>, AocError> {
+    let mut sizes = HashMap::new();
+    let mut cwd: Vec<String> = Vec::new();
+    for line in buf.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split(' ').collect();
+        match words[..] {
+            ["$", "cd", "/"] => cwd.clear(),
+            ["$", "cd", ".."] => {
+                cwd.pop().ok_or("Cannot go up from root")?;
+            }
+            ["$", "cd", dir] => cwd.push(dir.to_string()),
+            ["$", "ls"] | ["dir", _] => {}
+            [size, _name] => {
+                let size: usize = size.parse()?;
+                for depth in 0..=cwd.len() {
+                    *sizes.entry(cwd[..depth].join("/")).or_insert(0) += size;
+                }
+            }
+            _ => Err(format!("Could not parse line {line}"))?,
+        }
+    }
+    Ok(sizes)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    Ok(reference_sizes(buf)?
+        .values()
+        .filter(|s| **s < 100000)
+        .sum())
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    let sizes = reference_sizes(buf)?;
+    let used = sizes.get("").copied().unwrap_or(0);
+    let to_remove = used
+        .checked_sub(40000000)
+        .ok_or_else(|| AocError::no_solution("enough space is free already"))?;
+    sizes
+        .values()
+        .filter(|s| **s > to_remove)
+        .copied()
+        .min()
+        .ok_or_else(|| AocError::no_solution("no directory is large enough"))
 }
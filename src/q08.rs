@@ -1,69 +1,165 @@
-use itertools::{iproduct, Itertools};
-use std::{error::Error, io::BufRead};
+use itertools::Itertools;
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+};
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    grid::{Grid, SIDES},
+    parsing::{input_str, ToBufWrite},
+    random::Rng,
+};
+type Pos = crate::pos::Pos<i16>;
 
-struct Grid(Vec<Vec<u8>>);
+struct Forest(Grid<u8, i16>);
 
-impl FromBufRead for Grid {
-    type Error = std::io::Error;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        Ok(Grid(
-            br.lines()
-                .map_ok(|s| Vec::from_iter(s.bytes()))
-                .try_collect()?,
-        ))
+impl ToBufWrite for Forest {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        for row in self.0.view().rows() {
+            let row: String = row.values().map(|h| (b'0' + h) as char).collect();
+            writeln!(bw, "{row}")?;
+        }
+        Ok(())
     }
 }
 
-impl Grid {
+impl Forest {
+    fn parse(input: &[u8]) -> Result<Forest, AocError> {
+        let map = Grid::parse(input_str(input)?, None, "", |c| {
+            let height = c.to_digit(10).ok_or("Expected a tree height")?;
+            Ok(height as u8)
+        })?;
+        Ok(Forest(map.grid))
+    }
     fn get_highest_scenic_score(&self) -> Option<usize> {
-        iproduct!(0..self.0.len(), 0..self.0[0].len())
-            .map(|(r, c)| self.get_scenic_score(r, c))
+        self.0
+            .positions()
+            .map(|pos| self.get_scenic_score(pos))
             .max()
     }
-    fn get_scenic_score(&self, row: usize, col: usize) -> usize {
-        let height = self.0[row][col];
-        let vis_to_right = self.0[row][col + 1..]
-            .iter()
-            .position(|h| *h >= height)
-            .map_or(self.0[row].len() - col - 1, |p| p + 1);
-        let vis_to_left = self.0[row][0..col]
-            .iter()
-            .rev()
-            .position(|h| *h >= height)
-            .map_or(col, |p| p + 1);
-        let vis_to_top = self.0[0..row]
-            .iter()
-            .rev()
-            .position(|r| r[col] >= height)
-            .map_or(row, |p| p + 1);
-        let vis_to_bot = self.0[row + 1..]
-            .iter()
-            .position(|r| r[col] >= height)
-            .map_or(self.0.len() - row - 1, |p| p + 1);
-        vis_to_left * vis_to_right * vis_to_top * vis_to_bot
+    fn get_scenic_score(&self, pos: Pos) -> usize {
+        let height = self.0[&pos];
+        SIDES
+            .into_iter()
+            .map(|direction| {
+                let mut visible = 0;
+                for h in self.0.line(&pos, direction) {
+                    visible += 1;
+                    if *h >= height {
+                        break;
+                    }
+                }
+                visible
+            })
+            .product()
     }
     fn count_visible(&self) -> usize {
-        iproduct!(0..self.0.len(), 0..self.0[0].len())
-            .filter(|(r, c)| self.is_visible(*r, *c))
-            .count()
-    }
-    fn is_visible(&self, row: usize, col: usize) -> bool {
-        let height = self.0[row][col];
-        self.0[row][col + 1..].iter().all(|h| *h < height)
-            || self.0[row][..col].iter().all(|h| *h < height)
-            || self.0[0..row].iter().map(|r| r[col]).all(|h| h < height)
-            || self.0[row + 1..].iter().map(|r| r[col]).all(|h| h < height)
+        // looking along every row from both sides and every column from both ends, a tree is
+        // visible when it is higher than all trees before it
+        let mut visible = HashSet::new();
+        let mut view = self.0.view();
+        for _ in 0..4 {
+            for row in view.rows() {
+                let mut highest = None;
+                for (pos, height) in row.cells() {
+                    if Some(height) > highest {
+                        visible.insert(pos);
+                        highest = Some(height);
+                    }
+                }
+            }
+            view = view.rotate();
+        }
+        visible.len()
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    Ok(Grid::read(&mut buf)?.count_visible())
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let mut out = Vec::new();
+    Forest::parse(input)?.write(&mut out)?;
+    Ok(out)
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    Grid::read(&mut buf)?
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    Ok(Forest::parse(input)?.count_visible())
+}
+
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
+    Forest::parse(input)?
         .get_highest_scenic_score()
         .ok_or_else(|| "Grid is empty".into())
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    for _ in 0..size.max(1) {
+        let row: Vec<u8> = (0..size.max(1))
+            .map(|_| b'0' + rng.below(10) as u8)
+            .collect();
+        out.write_all(&row)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+const REFERENCE_DIRECTIONS: [(isize, isize); 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+
+// the trees from a tree to the edge in a direction, nearest first
+fn reference_line_of_sight(
+    grid: &[Vec<u8>],
+    row: usize,
+    col: usize,
+    direction: (isize, isize),
+) -> Vec<u8> {
+    let mut trees = Vec::new();
+    let (mut r, mut c) = (row as isize + direction.0, col as isize + direction.1);
+    while r >= 0 && c >= 0 && (r as usize) < grid.len() && (c as usize) < grid[r as usize].len() {
+        trees.push(grid[r as usize][c as usize]);
+        r += direction.0;
+        c += direction.1;
+    }
+    trees
+}
+
+fn reference_grid(buf: impl BufRead) -> Result<Vec<Vec<u8>>, AocError> {
+    Ok(buf.lines().map_ok(|l| l.into_bytes()).try_collect()?)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let grid = reference_grid(buf)?;
+    let mut count = 0;
+    for (row, trees) in grid.iter().enumerate() {
+        for (col, height) in trees.iter().enumerate() {
+            if REFERENCE_DIRECTIONS.iter().any(|d| {
+                reference_line_of_sight(&grid, row, col, *d)
+                    .iter()
+                    .all(|h| h < height)
+            }) {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    let grid = reference_grid(buf)?;
+    let mut best = None;
+    for (row, trees) in grid.iter().enumerate() {
+        for (col, height) in trees.iter().enumerate() {
+            let mut score = 1;
+            for direction in REFERENCE_DIRECTIONS {
+                let mut visible = 0;
+                for h in reference_line_of_sight(&grid, row, col, direction) {
+                    visible += 1;
+                    if h >= *height {
+                        break;
+                    }
+                }
+                score *= visible;
+            }
+            best = best.max(Some(score));
+        }
+    }
+    best.ok_or_else(|| "Grid is empty".into())
+}
@@ -1,6 +1,17 @@
-use std::{error::Error, io::BufRead, str::FromStr};
+use itertools::Itertools;
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
-use crate::parsing::parse_by_line;
+use crate::{
+    error::AocError,
+    grid::SparseGrid,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+};
 
 type Pos = crate::pos::Pos<i32>;
 
@@ -10,7 +21,7 @@ struct Move {
 }
 
 impl FromStr for Move {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.split_once(' ') {
             None => Err("No space in instruction".into()),
@@ -54,31 +65,48 @@ impl Pos {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match (self.direction.x, self.direction.y) {
+            (0, -1) => 'D',
+            (-1, 0) => 'L',
+            (1, 0) => 'R',
+            _ => 'U',
+        };
+        write!(f, "{direction} {}", self.steps)
+    }
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let moves: Vec<Move> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&moves, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
     let mut head = Pos::default();
     let mut tail = Pos::default();
-    let mut history = Vec::new();
-    history.push(tail);
-    for mov in parse_by_line::<Move>(buf) {
-        let mov = mov??;
+    let mut trail = SparseGrid::new(false);
+    trail[&tail] = true;
+    for mov in parse_lines::<Move>(input_str(input)?) {
+        let mov = mov?;
         for _ in 0..mov.steps {
             head += &mov.direction;
             if tail.follow(&head) {
-                history.push(tail);
+                trail[&tail] = true;
             }
         }
     }
-    history.sort();
-    history.dedup();
-    Ok(history.len())
+    Ok(trail.values().filter(|visited| **visited).count())
 }
 
-pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
     let mut knots = [Pos::default(); 10];
-    let mut history = Vec::new();
-    history.push(knots[9]);
-    for mov in parse_by_line::<Move>(buf) {
-        let mov = mov??;
+    let mut trail = SparseGrid::new(false);
+    trail[&knots[9]] = true;
+    for mov in parse_lines::<Move>(input_str(input)?) {
+        let mov = mov?;
         'mov: for _ in 0..mov.steps {
             knots[0] += &mov.direction;
             for i in 1..10 {
@@ -87,10 +115,55 @@ pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
                     continue 'mov;
                 }
             }
-            history.push(knots[9]);
+            trail[&knots[9]] = true;
+        }
+    }
+    Ok(trail.values().filter(|visited| **visited).count())
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    for _ in 0..size {
+        let direction = rng.choose(&['D', 'L', 'R', 'U']);
+        writeln!(out, "{direction} {}", rng.range(1..=20))?;
+    }
+    Ok(())
+}
+
+fn reference_rope(buf: impl BufRead, num_knots: usize) -> Result<usize, AocError> {
+    let mut knots = vec![(0i32, 0i32); num_knots];
+    let mut visited = HashSet::new();
+    visited.insert(knots[num_knots - 1]);
+    for line in buf.lines() {
+        let line = line?;
+        let (direction, steps) = line.split_once(' ').ok_or("No space in instruction")?;
+        let (dx, dy) = match direction {
+            "D" => (0, -1),
+            "L" => (-1, 0),
+            "R" => (1, 0),
+            "U" => (0, 1),
+            _ => Err("Could not parse Direction")?,
+        };
+        for _ in 0..steps.parse::<i32>()? {
+            knots[0].0 += dx;
+            knots[0].1 += dy;
+            for i in 1..num_knots {
+                let (x, y) = (knots[i - 1].0 - knots[i].0, knots[i - 1].1 - knots[i].1);
+                // a knot only moves when it no longer touches the previous one
+                if x.abs() > 1 || y.abs() > 1 {
+                    knots[i].0 += x.signum();
+                    knots[i].1 += y.signum();
+                }
+            }
+            visited.insert(knots[num_knots - 1]);
         }
     }
-    history.sort();
-    history.dedup();
-    Ok(history.len())
+    Ok(visited.len())
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_rope(buf, 2)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_rope(buf, 10)
 }
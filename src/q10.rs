@@ -1,7 +1,16 @@
 use itertools::Itertools;
-use std::{error::Error, io::BufRead, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
-use crate::parsing::parse_by_line;
+use crate::{
+    error::AocError,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+    trace,
+};
 
 enum Instruction {
     Noop,
@@ -9,7 +18,7 @@ enum Instruction {
 }
 
 impl FromStr for Instruction {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(line: &str) -> Result<Self, Self::Err> {
         match line {
             "noop" => Ok(Instruction::Noop),
@@ -20,6 +29,15 @@ impl FromStr for Instruction {
     }
 }
 
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Noop => write!(f, "noop"),
+            Instruction::Addx(value) => write!(f, "addx {value}"),
+        }
+    }
+}
+
 struct SignalGenerator<I> {
     iter: I,
     value: i32,
@@ -36,16 +54,15 @@ impl<I> SignalGenerator<I> {
     }
 }
 
-impl<E1, I> Iterator for SignalGenerator<I>
+impl<I> Iterator for SignalGenerator<I>
 where
-    E1: Error + 'static,
-    I: Iterator<Item = Result<Result<Instruction, Box<dyn Error>>, E1>>,
+    I: Iterator<Item = Result<Instruction, AocError>>,
 {
-    type Item = Result<i32, Box<dyn Error>>;
+    type Item = Result<i32, AocError>;
     fn next(&mut self) -> Option<Self::Item> {
         match self.cached.take() {
             None => self.iter.next().map(|r| {
-                match r?? {
+                match r? {
                     Instruction::Noop => {}
                     Instruction::Addx(n) => {
                         self.cached = Some(n);
@@ -61,8 +78,37 @@ where
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
-    let generator = SignalGenerator::new(parse_by_line::<Instruction>(buf));
+// the capital letters of the 4x6 font on the screen, every letter is followed by an empty column
+const FONT: [(&str, char); 18] = [
+    (".##.#..##..######..##..#", 'A'),
+    ("###.#..####.#..##..####.", 'B'),
+    (".##.#..##...#...#..#.##.", 'C'),
+    ("#####...###.#...#...####", 'E'),
+    ("#####...###.#...#...#...", 'F'),
+    (".##.#..##...#.###..#.###", 'G'),
+    ("#..##..######..##..##..#", 'H'),
+    (".###..#...#...#...#..###", 'I'),
+    ("..##...#...#...##..#.##.", 'J'),
+    ("#..##.#.##..#.#.#.#.#..#", 'K'),
+    ("#...#...#...#...#...####", 'L'),
+    (".##.#..##..##..##..#.##.", 'O'),
+    ("###.#..##..####.#...#...", 'P'),
+    ("###.#..##..####.#.#.#..#", 'R'),
+    (".####...#....##....####.", 'S'),
+    ("#..##..##..##..##..#.##.", 'U'),
+    ("#...#....#.#..#...#...#.", 'Y'),
+    ("####...#..#..#..#...####", 'Z'),
+];
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let instructions: Vec<Instruction> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&instructions, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i32, AocError> {
+    let generator = SignalGenerator::new(parse_lines::<Instruction>(input_str(input)?));
     let signals: Vec<_> = generator.try_collect()?;
     let cycles = [20, 60, 100, 140, 180, 220];
     let mut total = 0i32;
@@ -72,19 +118,102 @@ pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
     Ok(total)
 }
 
-pub fn b(buf: impl BufRead) -> Result<&'static str, Box<dyn Error>> {
-    let generator = SignalGenerator::new(parse_by_line::<Instruction>(buf));
-    for (pos, sprite_pos) in generator.enumerate() {
-        let pos = pos % 40;
-        if (pos as i32 - sprite_pos?).abs() < 2 {
-            print!("#");
+pub fn b(input: &[u8]) -> Result<String, AocError> {
+    let generator = SignalGenerator::new(parse_lines::<Instruction>(input_str(input)?));
+    let mut screen = [[false; 40]; 6];
+    for (cycle, sprite_pos) in generator.enumerate() {
+        // the whole program is read, a bad line after the last pixel is still an error
+        let sprite_pos = sprite_pos?;
+        if cycle < 240 && (((cycle % 40) as i32) - sprite_pos).abs() < 2 {
+            screen[cycle / 40][cycle % 40] = true;
+        }
+    }
+    let pixel = |on: &bool| if *on { '#' } else { '.' };
+    trace!(
+        Info,
+        "CRT screen:\n{}",
+        screen
+            .iter()
+            .map(|row| row.iter().map(pixel).collect::<String>())
+            .join("\n")
+    );
+    // unknown letters show up as ?
+    Ok((0..8)
+        .map(|letter| {
+            let pixels: String = screen
+                .iter()
+                .flat_map(|row| row[letter * 5..letter * 5 + 4].iter().map(pixel))
+                .collect();
+            FONT.iter()
+                .find(|(p, _)| *p == pixels)
+                .map_or('?', |(_, c)| *c)
+        })
+        .collect())
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // a reads the signal up to cycle 220, b draws 240 pixels
+    let mut cycles = 0;
+    let mut instructions = 0;
+    while instructions < size || cycles < 240 {
+        if rng.ratio(1, 3) {
+            writeln!(out, "noop")?;
+            cycles += 1;
         } else {
-            print!(" ");
+            writeln!(out, "addx {}", rng.range(-20..=20))?;
+            cycles += 2;
         }
-        if pos == 39 {
-            println!();
+        instructions += 1;
+    }
+    Ok(())
+}
+
+// the value of X during every cycle
+fn reference_signal(buf: impl BufRead) -> Result<Vec<i32>, AocError> {
+    let mut x = 1;
+    let mut signal = Vec::new();
+    for line in buf.lines() {
+        let line = line?;
+        if line == "noop" {
+            signal.push(x);
+        } else {
+            let v: i32 = line.strip_prefix("addx ").ok_or("Expected addx")?.parse()?;
+            signal.push(x);
+            signal.push(x);
+            x += v;
+        }
+    }
+    Ok(signal)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i32, AocError> {
+    let signal = reference_signal(buf)?;
+    let mut total = 0;
+    for cycle in [20, 60, 100, 140, 180, 220] {
+        total += cycle as i32 * signal.get(cycle - 1).ok_or("Too few cycles")?;
+    }
+    Ok(total)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<String, AocError> {
+    let signal = reference_signal(buf)?;
+    let mut screen = [[b'.'; 40]; 6];
+    for (cycle, x) in signal.iter().enumerate().take(240) {
+        let column = (cycle % 40) as i32;
+        if (column - x).abs() < 2 {
+            screen[cycle / 40][cycle % 40] = b'#';
         }
     }
-    // I did not feel like implementing OCR :)
-    Ok("BUCACBUZ")
+    // unknown letters show up as ?
+    Ok((0..8)
+        .map(|letter| {
+            let pixels: String = screen
+                .iter()
+                .map(|row| std::str::from_utf8(&row[letter * 5..letter * 5 + 4]).unwrap()) // only ASCII
+                .collect();
+            FONT.iter()
+                .find(|(p, _)| **p == pixels)
+                .map_or('?', |(_, c)| *c)
+        })
+        .collect())
 }
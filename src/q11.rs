@@ -1,11 +1,32 @@
 use itertools::Itertools;
-use std::{error::Error, io::BufRead, num::ParseIntError};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{BufRead, Write},
+    str::FromStr,
+};
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    parsing::{ints_vec, read_sections, write_sections, FromBufRead, Line, ToBufWrite},
+    random::Rng,
+    scan,
+};
 
-type Op = Box<dyn Fn(i64) -> i64>;
+#[derive(Clone, Copy)]
+enum Operand {
+    Old,
+    Value(i64),
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Add(Operand),
+    Mul(Operand),
+}
 
 struct Monkey {
+    id: usize,
     items: Vec<i64>,
     op: Op,
     divisible_by: i64,
@@ -14,59 +35,88 @@ struct Monkey {
     inspected: usize,
 }
 
-fn read_op(line: &str) -> Result<Op, Box<dyn Error>> {
-    let (op, rhs) = line.split_once(' ').ok_or("Expected one space")?;
-    let get_op = |op: fn(_, _) -> _| -> Result<Op, ParseIntError> {
-        if rhs == "old" {
-            Ok(Box::new(move |i| op(i, i)))
-        } else {
-            let r = rhs.parse::<i64>()?;
-            Ok(Box::new(move |i| op(i, r)))
+impl Op {
+    fn apply(&self, old: i64) -> i64 {
+        let value = |operand| match operand {
+            Operand::Old => old,
+            Operand::Value(value) => value,
+        };
+        match *self {
+            Op::Add(operand) => old + value(operand),
+            Op::Mul(operand) => old * value(operand),
+        }
+    }
+}
+
+impl FromStr for Op {
+    type Err = AocError;
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (op, rhs) = line.split_once(' ').ok_or("Expected one space")?;
+        let operand = match rhs {
+            "old" => Operand::Old,
+            value => Operand::Value(value.parse()?),
+        };
+        match op {
+            "+" => Ok(Op::Add(operand)),
+            "*" => Ok(Op::Mul(operand)),
+            _ => Err(format!("Unknown operator {op}"))?,
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (op, operand) = match self {
+            Op::Add(operand) => ('+', operand),
+            Op::Mul(operand) => ('*', operand),
+        };
+        match operand {
+            Operand::Old => write!(f, "{op} old"),
+            Operand::Value(value) => write!(f, "{op} {value}"),
         }
-    };
-    Ok(match op {
-        "+" => get_op(|i, j| i + j)?,
-        "*" => get_op(|i, j| i * j)?,
-        _ => panic!(),
-    })
+    }
 }
 
 impl FromBufRead for Monkey {
-    type Error = Box<dyn Error>;
+    type Error = AocError;
     fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let mut read_line = |prefix| -> Result<String, Box<dyn Error>> {
-            let mut line = String::new();
-            while line.len() < 3 {
-                line.clear();
-                br.read_line(&mut line)?;
-            }
-            let line = line.strip_suffix('\n').unwrap_or(&line);
-            let line = line
-                .strip_prefix(prefix)
-                .ok_or(format!("Could not read prefix {prefix}"))?;
-            Ok(line.into())
+        let [header, items, operation, test, if_true, if_false] = <[Line<String>; 6]>::read(br)?;
+        let field = |line: &Line<String>, prefix| -> Result<String, AocError> {
+            let field = line.0.strip_prefix(prefix);
+            Ok(field
+                .ok_or(format!("Could not read prefix {prefix}"))?
+                .into())
         };
-        let _ = read_line("Monkey ")?;
-        let items = read_line("  Starting items: ")?
-            .split(", ")
-            .map(|s| s.parse())
-            .try_collect()?;
+        let (id,) = scan!(&header.0, "Monkey {}:", usize)?;
+        let items = ints_vec(&field(&items, "  Starting items: ")?)?;
         Ok(Monkey {
+            id,
             items,
-            op: read_op(&read_line("  Operation: new = old ")?)?,
-            divisible_by: read_line("  Test: divisible by ")?.parse()?,
-            true_monkey: read_line("    If true: throw to monkey ")?.parse()?,
-            false_monkey: read_line("    If false: throw to monkey ")?.parse()?,
+            op: field(&operation, "  Operation: new = old ")?.parse()?,
+            divisible_by: field(&test, "  Test: divisible by ")?.parse()?,
+            true_monkey: field(&if_true, "    If true: throw to monkey ")?.parse()?,
+            false_monkey: field(&if_false, "    If false: throw to monkey ")?.parse()?,
             inspected: 0,
         })
     }
 }
 
+impl ToBufWrite for Monkey {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        writeln!(bw, "Monkey {}:", self.id)?;
+        writeln!(bw, "  Starting items: {}", self.items.iter().join(", "))?;
+        writeln!(bw, "  Operation: new = old {}", self.op)?;
+        writeln!(bw, "  Test: divisible by {}", self.divisible_by)?;
+        writeln!(bw, "    If true: throw to monkey {}", self.true_monkey)?;
+        writeln!(bw, "    If false: throw to monkey {}", self.false_monkey)
+    }
+}
+
 impl Monkey {
     fn give_up_a(&mut self) -> Option<(usize, i64)> {
         self.items.pop().map(|i| {
             self.inspected += 1;
-            let v = (self.op)(i) / 3;
+            let v = self.op.apply(i) / 3;
             if v % self.divisible_by == 0 {
                 (self.true_monkey, v)
             } else {
@@ -77,7 +127,7 @@ impl Monkey {
     fn give_up_b(&mut self) -> Option<(usize, i64)> {
         self.items.pop().map(|i| {
             self.inspected += 1;
-            let v = (self.op)(i);
+            let v = self.op.apply(i);
             if v % self.divisible_by == 0 {
                 (self.true_monkey, v)
             } else {
@@ -87,8 +137,15 @@ impl Monkey {
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let mut monkeys: Vec<Monkey> = Monkey::read_iter(&mut buf).try_collect()?;
+pub fn reformat(mut input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let monkeys: Vec<Monkey> = read_sections(&mut input).try_collect()?;
+    let mut out = Vec::new();
+    write_sections(&monkeys, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(mut input: &[u8]) -> Result<usize, AocError> {
+    let mut monkeys: Vec<Monkey> = read_sections(&mut input).try_collect()?;
     for _ in 0..20 {
         for i in 0..monkeys.len() {
             while let Some((new_monkey, item)) = monkeys[i].give_up_a() {
@@ -103,9 +160,12 @@ pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     Ok(a1 * a2)
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let mut monkeys: Vec<Monkey> = Monkey::read_iter(&mut buf).try_collect()?;
-    let common_factor: i64 = monkeys.iter().map(|m| m.divisible_by).product();
+pub fn b(mut input: &[u8]) -> Result<usize, AocError> {
+    let mut monkeys: Vec<Monkey> = read_sections(&mut input).try_collect()?;
+    let common_factor = monkeys
+        .iter()
+        .try_fold(1i64, |product, m| product.checked_mul(m.divisible_by))
+        .ok_or_else(|| AocError::overflow("the product of all divisors"))?;
     for _ in 0..10000 {
         for i in 0..monkeys.len() {
             while let Some((new_monkey, mut item)) = monkeys[i].give_up_b() {
@@ -122,3 +182,117 @@ pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     let a2 = activity.pop().ok_or("Too few activities")?;
     Ok(a1 * a2)
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // size is the total number of items. b multiplies all divisors, so more than 8 monkeys
+    // could overflow. a never reduces worry levels, so only operations that cannot make
+    // them grow after the division by 3 are used (the real input relies on lucky routing).
+    let num_monkeys = size.clamp(2, 8);
+    let mut divisors = [2, 3, 5, 7, 11, 13, 17, 19];
+    rng.shuffle(&mut divisors);
+    for (monkey, divisor) in divisors.iter().enumerate().take(num_monkeys) {
+        if monkey > 0 {
+            writeln!(out)?;
+        }
+        // every monkey needs at least one item
+        let num_items = size.max(num_monkeys) / num_monkeys
+            + usize::from(monkey < size.max(num_monkeys) % num_monkeys);
+        let items = (0..num_items)
+            .map(|_| rng.range(50..=99).to_string())
+            .collect::<Vec<_>>();
+        let operation = match rng.below(3) {
+            0 => "+ old".to_string(),
+            1 => format!("* {}", rng.range(2..=3)),
+            _ => format!("+ {}", rng.range(1..=8)),
+        };
+        // a monkey never throws to itself
+        let true_monkey = (monkey + 1 + rng.below(num_monkeys - 1)) % num_monkeys;
+        let mut false_monkey = (monkey + 1 + rng.below(num_monkeys - 1)) % num_monkeys;
+        if false_monkey == true_monkey && num_monkeys > 2 {
+            false_monkey = (false_monkey + 1) % num_monkeys;
+            if false_monkey == monkey {
+                false_monkey = (false_monkey + 1) % num_monkeys;
+            }
+        }
+        writeln!(out, "Monkey {monkey}:")?;
+        writeln!(out, "  Starting items: {}", items.join(", "))?;
+        writeln!(out, "  Operation: new = old {operation}")?;
+        writeln!(out, "  Test: divisible by {divisor}")?;
+        writeln!(out, "    If true: throw to monkey {true_monkey}")?;
+        writeln!(out, "    If false: throw to monkey {false_monkey}")?;
+    }
+    Ok(())
+}
+
+struct ReferenceMonkey {
+    items: VecDeque<i128>,
+    operation: (String, String),
+    divisible_by: i128,
+    targets: [usize; 2],
+}
+
+fn reference_monkey_business(
+    buf: impl BufRead,
+    rounds: usize,
+    relief: bool,
+) -> Result<usize, AocError> {
+    let lines = buf.lines().collect::<Result<Vec<String>, _>>()?;
+    let last_word = |line: &String| line.rsplit(' ').next().unwrap_or_default().to_string(); // rsplit always returns something
+    let mut monkeys = Vec::new();
+    for block in lines.split(|l| l.is_empty()).filter(|b| !b.is_empty()) {
+        let [_, items, operation, test, if_true, if_false] = block else {
+            Err("Expected 6 lines per monkey")?
+        };
+        let (_, items) = items.split_once(": ").ok_or("Expected items")?;
+        let (_, operation) = operation.split_once("old ").ok_or("Expected operation")?;
+        let (operator, operand) = operation.split_once(' ').ok_or("Expected operand")?;
+        monkeys.push(ReferenceMonkey {
+            items: items.split(", ").map(|i| i.parse()).try_collect()?,
+            operation: (operator.to_string(), operand.to_string()),
+            divisible_by: last_word(test).parse()?,
+            targets: [last_word(if_false).parse()?, last_word(if_true).parse()?],
+        });
+    }
+    // keeping worry levels modulo the product of all tests does not change any test result
+    let modulo: i128 = monkeys.iter().map(|m| m.divisible_by).product();
+    let mut inspected = vec![0; monkeys.len()];
+    for _ in 0..rounds {
+        for i in 0..monkeys.len() {
+            while let Some(old) = monkeys[i].items.pop_front() {
+                inspected[i] += 1;
+                let (operator, operand) = &monkeys[i].operation;
+                let operand = if operand == "old" {
+                    old
+                } else {
+                    operand.parse()?
+                };
+                let mut new = match operator.as_str() {
+                    "+" => old + operand,
+                    "*" => old * operand,
+                    _ => Err("Unexpected operator")?,
+                };
+                if relief {
+                    new /= 3;
+                } else {
+                    new %= modulo;
+                }
+                let target = monkeys[i].targets[usize::from(new % monkeys[i].divisible_by == 0)];
+                monkeys
+                    .get_mut(target)
+                    .ok_or("Unknown monkey")?
+                    .items
+                    .push_back(new);
+            }
+        }
+    }
+    inspected.sort();
+    Ok(inspected.iter().rev().take(2).product())
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_monkey_business(buf, 20, true)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_monkey_business(buf, 10000, false)
+}
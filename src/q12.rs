@@ -1,74 +1,58 @@
-use std::{cmp::Reverse, collections::BinaryHeap, error::Error, io::BufRead};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+    io::{BufRead, Write},
+};
 
-use itertools::repeat_n;
-
-use crate::{grid::Grid, parsing::FromBufRead};
+use crate::{
+    error::AocError,
+    grid::{Grid, SIDES},
+    parsing::{input_str, ToBufWrite},
+    random::Rng,
+    trace,
+};
 type Pos = crate::pos::Pos<i16>;
 
-const DIRECTIONS: [Pos; 4] = [
-    Pos { x: 1, y: 0 },
-    Pos { x: -1, y: 0 },
-    Pos { x: 0, y: 1 },
-    Pos { x: 0, y: -1 },
-];
-
 struct HeightGrid {
     heights: Grid<i16, i16>,
     start_pos: Pos,
     end_pos: Pos,
 }
 
-impl FromBufRead for HeightGrid {
-    type Error = Box<dyn Error>;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let mut row_size = 0;
-        let mut h = Vec::new();
-        let mut start_pos = None;
-        let mut end_pos = None;
-        const UNREACHABLE: i16 = 32000;
-        for (y, line) in br.lines().enumerate() {
-            let line = line?;
-            if row_size != 0 && row_size != line.len() {
-                Err("Inconsistent row size")?;
-            }
-            row_size = line.len();
-            h.push(UNREACHABLE);
-            for (x, c) in line.as_bytes().iter().enumerate() {
-                h.push(match c {
-                    b'S' => {
-                        start_pos = Some(Pos {
-                            x: x as i16,
-                            y: y as i16,
-                        });
-                        0
-                    }
-                    b'E' => {
-                        end_pos = Some(Pos {
-                            x: x as i16,
-                            y: y as i16,
-                        });
-                        25
+impl HeightGrid {
+    fn parse(input: &[u8]) -> Result<HeightGrid, AocError> {
+        let map = Grid::parse(input_str(input)?, None, "SE", |c| match c {
+            'S' => Ok(0),
+            'E' => Ok(25),
+            'a'..='z' => Ok(c as i16 - 'a' as i16),
+            _ => Err("Expected a height from a to z".into()),
+        })?;
+        Ok(HeightGrid {
+            start_pos: map.marker('S')?,
+            end_pos: map.marker('E')?,
+            heights: map.grid,
+        })
+    }
+}
+
+impl ToBufWrite for HeightGrid {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        for row in self.heights.view().rows() {
+            let row: String = row
+                .cells()
+                .map(|(pos, height)| {
+                    if pos == self.start_pos {
+                        'S'
+                    } else if pos == self.end_pos {
+                        'E'
+                    } else {
+                        (b'a' + *height as u8) as char
                     }
-                    b => (b - b'a').into(),
                 })
-            }
-            h.push(UNREACHABLE);
+                .collect();
+            writeln!(bw, "{row}")?;
         }
-        row_size += 2;
-        h.splice(0..0, repeat_n(UNREACHABLE, row_size));
-        h.extend(repeat_n(UNREACHABLE, row_size));
-        let start_pos = &start_pos.ok_or("Did not find start pos in grid")? + &Pos { x: 1, y: 1 };
-        let end_pos = &end_pos.ok_or("Did not find end pos in grid")? + &Pos { x: 1, y: 1 };
-        let size = Pos {
-            x: row_size as i16,
-            y: (h.len() / row_size) as i16,
-        };
-        let heights = Grid::from_values(h, size);
-        Ok(HeightGrid {
-            heights,
-            start_pos,
-            end_pos,
-        })
+        Ok(())
     }
 }
 
@@ -79,22 +63,21 @@ struct Solver {
 }
 
 impl Solver {
-    fn new(grid: HeightGrid) -> Solver {
-        let solved = Grid::from_value(32000, grid.heights.size());
-        Solver {
+    fn new(grid: HeightGrid) -> Result<Solver, AocError> {
+        let solved = Grid::from_value(32000, grid.heights.size())?;
+        Ok(Solver {
             grid,
             solved,
             to_solve: Default::default(),
-        }
+        })
     }
-    fn solve_a(&mut self) -> Result<usize, Box<dyn Error>> {
+    fn solve_a(&mut self) -> Result<usize, AocError> {
         self.to_solve.clear();
         self.to_solve.push(Reverse((0, self.grid.start_pos)));
         while let Some(Reverse((rank, pos))) = self.to_solve.pop() {
             if rank < self.solved[&pos] {
                 self.solved[&pos] = rank;
-                for dir in DIRECTIONS {
-                    let next_pos = &pos + &dir;
+                for next_pos in self.grid.heights.neighbours(&pos, SIDES) {
                     if self.grid.heights[&next_pos] > self.grid.heights[&pos] + 1 {
                         continue;
                     }
@@ -108,24 +91,20 @@ impl Solver {
                 }
             }
         }
-        println!("{}", self.solved);
-        Err("No solution found".into())
+        trace!(Debug, "Ranks when giving up:\n{}", self.solved);
+        Err(AocError::no_solution("no path to the end"))
     }
-    fn solve_b(&mut self) -> Result<usize, Box<dyn Error>> {
+    fn solve_b(&mut self) -> Result<usize, AocError> {
         self.to_solve.clear();
         self.to_solve.push(Reverse((0, self.grid.end_pos)));
         while let Some(Reverse((rank, pos))) = self.to_solve.pop() {
             if rank < self.solved[&pos] {
                 self.solved[&pos] = rank;
-                for dir in DIRECTIONS {
-                    let next_pos = &pos + &dir;
+                for next_pos in self.grid.heights.neighbours(&pos, SIDES) {
                     if self.solved[&next_pos] <= rank {
                         continue;
                     }
                     let next_height = self.grid.heights[&next_pos];
-                    if next_height == 32000 {
-                        continue;
-                    }
                     if self.grid.heights[&pos] > next_height + 1 {
                         continue;
                     }
@@ -136,19 +115,144 @@ impl Solver {
                 }
             }
         }
-        println!("{}", self.solved);
-        Err("No solution found".into())
+        trace!(Debug, "Ranks when giving up:\n{}", self.solved);
+        Err(AocError::no_solution("no path to the end"))
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let grid = HeightGrid::read(&mut buf)?;
-    let mut solver = Solver::new(grid);
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let mut out = Vec::new();
+    HeightGrid::parse(input)?.write(&mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let grid = HeightGrid::parse(input)?;
+    let mut solver = Solver::new(grid)?;
     solver.solve_a()
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let grid = HeightGrid::read(&mut buf)?;
-    let mut solver = Solver::new(grid);
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
+    let grid = HeightGrid::parse(input)?;
+    let mut solver = Solver::new(grid)?;
     solver.solve_b()
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // the height rises from S in the top left to E in the bottom right by at most 1 per step,
+    // which needs at least 25 steps. Cells off a random staircase path are sunk into pits.
+    let size = size.max(14);
+    let diagonal = 2 * size - 2;
+    let mut heights: Vec<Vec<u8>> = (0..size)
+        .map(|y| (0..size).map(|x| (25 * (x + y) / diagonal) as u8).collect())
+        .collect();
+    let mut on_path = vec![vec![false; size]; size];
+    let (mut x, mut y) = (0, 0);
+    on_path[0][0] = true;
+    while (x, y) != (size - 1, size - 1) {
+        if y == size - 1 || (x < size - 1 && rng.ratio(1, 2)) {
+            x += 1;
+        } else {
+            y += 1;
+        }
+        on_path[y][x] = true;
+    }
+    for (row, path_row) in heights.iter_mut().zip(on_path.iter()) {
+        for (height, on_path) in row.iter_mut().zip(path_row.iter()) {
+            if !on_path && rng.ratio(1, 3) {
+                *height -= rng.below(*height as usize + 1) as u8;
+            }
+        }
+    }
+    for (y, row) in heights.iter().enumerate() {
+        let line: Vec<u8> = row
+            .iter()
+            .enumerate()
+            .map(|(x, height)| match (x, y) {
+                (0, 0) => b'S',
+                (x, y) if x == size - 1 && y == size - 1 => b'E',
+                _ => b'a' + height,
+            })
+            .collect();
+        out.write_all(&line)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn reference_height(c: u8) -> u8 {
+    match c {
+        b'S' => b'a',
+        b'E' => b'z',
+        c => c,
+    }
+}
+
+// the fewest steps from any of the starts to E with a breadth first search
+fn reference_steps(grid: &[Vec<u8>], starts: Vec<(usize, usize)>) -> Option<usize> {
+    let mut steps = vec![vec![None; grid[0].len()]; grid.len()];
+    for (x, y) in starts.iter() {
+        steps[*y][*x] = Some(0);
+    }
+    let mut queue = VecDeque::from(starts);
+    while let Some((x, y)) = queue.pop_front() {
+        let s = steps[y][x]?;
+        if grid[y][x] == b'E' {
+            return Some(s);
+        }
+        let neighbours = [
+            (x.wrapping_sub(1), y),
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+        ];
+        for (nx, ny) in neighbours {
+            if ny < grid.len()
+                && nx < grid[ny].len()
+                && steps[ny][nx].is_none()
+                && reference_height(grid[ny][nx]) <= reference_height(grid[y][x]) + 1
+            {
+                steps[ny][nx] = Some(s + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    None
+}
+
+fn reference_grid(buf: impl BufRead) -> Result<Vec<Vec<u8>>, AocError> {
+    let grid = buf
+        .lines()
+        .map(|l| l.map(String::into_bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+    if grid.is_empty() || grid.iter().any(|row| row.len() != grid[0].len()) {
+        Err("Inconsistent row size")?;
+    }
+    Ok(grid)
+}
+
+fn reference_starts(grid: &[Vec<u8>], start: impl Fn(u8) -> bool) -> Vec<(usize, usize)> {
+    let mut starts = Vec::new();
+    for (y, row) in grid.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            if start(*c) {
+                starts.push((x, y));
+            }
+        }
+    }
+    starts
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let grid = reference_grid(buf)?;
+    let start = *reference_starts(&grid, |c| c == b'S')
+        .first()
+        .ok_or("Did not find start pos in grid")?;
+    reference_steps(&grid, vec![start]).ok_or_else(|| AocError::no_solution("no path to the end"))
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    let grid = reference_grid(buf)?;
+    let starts = reference_starts(&grid, |c| reference_height(c) == b'a');
+    reference_steps(&grid, starts).ok_or_else(|| AocError::no_solution("no path to the end"))
+}
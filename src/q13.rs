@@ -1,7 +1,20 @@
 use itertools::Itertools;
-use std::{cmp::Ordering, error::Error, io::BufRead, iter::once, str::FromStr};
+use std::{
+    cmp::Ordering,
+    fmt::Display,
+    io::{BufRead, Write},
+    iter::once,
+    str::FromStr,
+};
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    parsing::{
+        combinators::{int, lit, parse_all, separated, PResult, Parser},
+        read_sections, write_sections, Line,
+    },
+    random::Rng,
+};
 
 enum Packet {
     Int(i32),
@@ -35,34 +48,12 @@ impl PartialEq<Packet> for Packet {
 }
 
 impl Packet {
-    fn parse(mut s: &str) -> Result<(Packet, &str), Box<dyn Error>> {
-        if s.is_empty() {
-            Err("No data")?;
-        }
-        match s.as_bytes()[0] {
-            b'[' => {
-                let mut subs = Vec::new();
-                loop {
-                    s = &s[1..];
-                    if s.as_bytes()[0] != b']' {
-                        let (sub, remaining) = Packet::parse(s)?;
-                        subs.push(sub);
-                        s = remaining;
-                    }
-                    match s.as_bytes()[0] {
-                        b']' => return Ok((Packet::List(subs), &s[1..])),
-                        b',' => {}
-                        _ => Err("Unexpected token")?,
-                    }
-                }
-            }
-            _ => {
-                let position = s
-                    .find(|c| (c == ',') || (c == ']'))
-                    .ok_or("No delimiter found")?;
-                Ok((Packet::Int(s[..position].parse()?), &s[position..]))
-            }
-        }
+    // packet = '[' (packet (',' packet)*)? ']' | integer
+    fn parse(input: &str) -> PResult<'_, Packet> {
+        (lit("["), separated(Packet::parse, lit(",")), lit("]"))
+            .map(|(_, packets, _)| Packet::List(packets))
+            .or(int().map(Packet::Int))
+            .parse(input)
     }
 
     fn partial_cmp_iters<'a>(
@@ -83,36 +74,34 @@ impl Packet {
     }
 }
 
-impl FromStr for Packet {
-    type Err = Box<dyn Error>;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (packet, remaining) = Packet::parse(s)?;
-        if !remaining.is_empty() {
-            Err(format!("Did not parse full input: >{remaining}<"))?;
+impl Display for Packet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Packet::Int(value) => write!(f, "{value}"),
+            Packet::List(packets) => write!(f, "[{}]", packets.iter().join(",")),
         }
-        Ok(packet)
     }
 }
 
-impl FromBufRead for (Packet, Packet) {
-    type Error = Box<dyn Error>;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let mut line = String::new();
-        br.read_line(&mut line)?;
-        let lhs = line.strip_suffix('\n').unwrap_or(&line).parse()?;
-        line.clear();
-        br.read_line(&mut line)?;
-        let rhs = line.strip_suffix('\n').unwrap_or(&line).parse()?;
-        br.read_line(&mut line)?;
-        Ok((lhs, rhs))
+impl FromStr for Packet {
+    type Err = AocError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(parse_all(Packet::parse, s)?)
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn reformat(mut input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let pairs: Vec<(Line<Packet>, Line<Packet>)> = read_sections(&mut input).try_collect()?;
+    let mut out = Vec::new();
+    write_sections(&pairs, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(mut input: &[u8]) -> Result<usize, AocError> {
     let mut sum = 0;
-    for i in <(Packet, Packet) as FromBufRead>::read_iter(&mut buf)
+    for i in read_sections::<(Line<Packet>, Line<Packet>)>(&mut input)
         .enumerate()
-        .map(|(i, res)| res.map(|(lhs, rhs)| (i + 1, lhs < rhs)))
+        .map(|(i, res)| res.map(|(lhs, rhs)| (i + 1, lhs.0 < rhs.0)))
         .filter_ok(|(_index, smaller)| *smaller)
         .map_ok(|(index, _smaller)| index)
     {
@@ -121,23 +110,153 @@ pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     Ok(sum)
 }
 
-pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let packets = buf
-        .lines()
-        .filter_ok(|l| !l.is_empty())
-        .map_ok(|l| l.parse())
-        .try_collect::<Result<_, _>, Result<_, _>, _>();
-    let mut packets: Vec<Packet> = packets??;
+pub fn b(mut input: &[u8]) -> Result<usize, AocError> {
+    let mut packets: Vec<Packet> = read_sections::<(Line<Packet>, Line<Packet>)>(&mut input)
+        .map_ok(|(lhs, rhs)| [lhs.0, rhs.0])
+        .flatten_ok()
+        .try_collect()?;
     packets.sort();
     let pos1 = packets
         .binary_search(&"[[2]]".parse()?)
         .err()
-        .ok_or("Could not find 2")?
+        .ok_or_else(|| AocError::no_solution("a packet equals divider [[2]]"))?
         + 1;
     let pos2 = packets
         .binary_search(&"[[6]]".parse()?)
         .err()
-        .ok_or("Could not find 6")?
+        .ok_or_else(|| AocError::no_solution("a packet equals divider [[6]]"))?
         + 2;
     Ok(pos1 * pos2)
 }
+
+fn generate_packet(rng: &mut Rng, depth: usize, out: &mut String) {
+    out.push('[');
+    for i in 0..rng.below(6) {
+        if i > 0 {
+            out.push(',');
+        }
+        if depth < 4 && rng.ratio(1, 3) {
+            generate_packet(rng, depth + 1, out);
+        } else {
+            out.push_str(&rng.below(11).to_string());
+        }
+    }
+    out.push(']');
+}
+
+fn write_packet(rng: &mut Rng, out: &mut impl Write) -> std::io::Result<()> {
+    // b cannot handle packets that are equal to a divider packet
+    let dividers: [Packet; 2] = ["[[2]]".parse().unwrap(), "[[6]]".parse().unwrap()];
+    let mut packet = String::new();
+    loop {
+        packet.clear();
+        generate_packet(rng, 0, &mut packet);
+        let parsed: Packet = packet.parse().unwrap(); // generated packets are always valid
+        if !dividers.contains(&parsed) {
+            return writeln!(out, "{packet}");
+        }
+    }
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    for pair in 0..size {
+        if pair > 0 {
+            writeln!(out)?;
+        }
+        write_packet(rng, out)?;
+        write_packet(rng, out)?;
+    }
+    Ok(())
+}
+
+enum ReferencePacket {
+    Int(i32),
+    List(Vec<ReferencePacket>),
+}
+
+fn reference_parse(line: &str) -> Result<ReferencePacket, AocError> {
+    let mut open_lists: Vec<Vec<ReferencePacket>> = Vec::new();
+    let mut packet = None;
+    let mut number = String::new();
+    for c in line.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        if !number.is_empty() {
+            let list = open_lists.last_mut().ok_or("Number outside list")?;
+            list.push(ReferencePacket::Int(number.parse()?));
+            number.clear();
+        }
+        match c {
+            '[' => open_lists.push(Vec::new()),
+            ']' => {
+                let list = ReferencePacket::List(open_lists.pop().ok_or("Unexpected ]")?);
+                match open_lists.last_mut() {
+                    Some(parent) => parent.push(list),
+                    None if packet.is_none() => packet = Some(list),
+                    None => Err("More than one packet")?,
+                }
+            }
+            ',' if !open_lists.is_empty() => {}
+            c => Err(format!("Unexpected character {c}"))?,
+        }
+    }
+    if !open_lists.is_empty() || !number.is_empty() {
+        Err("Unfinished packet")?;
+    }
+    packet.ok_or_else(|| "No packet".into())
+}
+
+fn reference_compare(left: &ReferencePacket, right: &ReferencePacket) -> Ordering {
+    use ReferencePacket::*;
+    match (left, right) {
+        (Int(l), Int(r)) => l.cmp(r),
+        (List(l), List(r)) => {
+            for (l, r) in l.iter().zip(r.iter()) {
+                match reference_compare(l, r) {
+                    Ordering::Equal => {}
+                    o => return o,
+                }
+            }
+            l.len().cmp(&r.len())
+        }
+        (Int(l), List(_)) => reference_compare(&List(vec![Int(*l)]), right),
+        (List(_), Int(r)) => reference_compare(left, &List(vec![Int(*r)])),
+    }
+}
+
+fn reference_packets(buf: impl BufRead) -> Result<Vec<ReferencePacket>, AocError> {
+    let mut packets = Vec::new();
+    for line in buf.lines() {
+        let line = line?;
+        if !line.is_empty() {
+            packets.push(reference_parse(&line)?);
+        }
+    }
+    Ok(packets)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let mut sum = 0;
+    for (index, pair) in reference_packets(buf)?.chunks_exact(2).enumerate() {
+        if reference_compare(&pair[0], &pair[1]) == Ordering::Less {
+            sum += index + 1;
+        }
+    }
+    Ok(sum)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    // the position of a divider is one more than the number of packets before it
+    let packets = reference_packets(buf)?;
+    let position = |divider: &str| -> Result<usize, AocError> {
+        let divider = reference_parse(divider)?;
+        Ok(packets
+            .iter()
+            .filter(|p| reference_compare(p, &divider) == Ordering::Less)
+            .count()
+            + 1)
+    };
+    Ok(position("[[2]]")? * (position("[[6]]")? + 1))
+}
@@ -1,17 +1,18 @@
 use itertools::Itertools;
 use std::{
-    error::Error,
+    collections::HashSet,
     fmt::Display,
-    io::BufRead,
-    ops::{Index, IndexMut},
+    io::{BufRead, Write},
     str::FromStr,
 };
-// use std::{thread::sleep, time::Duration};
 
 use crate::{
-    grid::Grid,
-    parsing::{parse_by_line, FromBufRead},
+    error::AocError,
+    grid::{GridAccess, OffsetGrid},
+    parsing::{input_str, parse_lines, write_lines},
     pos::Pos,
+    random::Rng,
+    trace,
 };
 
 impl Pos<i16> {
@@ -30,10 +31,13 @@ enum Material {
     Sand,
 }
 
+const SOURCE: Pos<i16> = Pos { x: 500, y: 0 };
+
 #[derive(Clone)]
-struct Cave {
-    grid: Grid<Material, i16>,
-    offset: Pos<i16>,
+struct Cave<G> {
+    grid: G,
+    // sand that falls this deep rests on the floor
+    floor: i16,
 }
 
 enum FillResult {
@@ -42,41 +46,51 @@ enum FillResult {
     FloorAtX(i16),
 }
 
-impl Cave {
-    fn new(rocks: &Vec<Rock>, min: Pos<i16>, max: Pos<i16>) -> Cave {
-        let mut cave = Cave {
-            grid: Grid::from_value(Material::Air, &max - &min),
-            offset: min,
-        };
+// The deepest row sand can rest in. Sand spreads at most as far to the sides as it falls,
+// so the floor reaches that far from the source as well.
+fn floor(rocks: &[Rock]) -> Result<i16, AocError> {
+    let maxy = rocks
+        .iter()
+        .flat_map(|rock| rock.0.iter().map(|p| p.y))
+        .max()
+        .ok_or("Too few rocks")?;
+    if maxy > i16::MAX - SOURCE.x - 2 {
+        Err(format!(
+            "Rocks at depth {maxy} are too deep for the floor to fit"
+        ))?;
+    }
+    Ok(maxy + 1)
+}
+
+impl<G: GridAccess<Material, i16>> Cave<G> {
+    fn new(rocks: &[Rock], mut grid: G) -> Result<Cave<G>, AocError> {
+        let floor = floor(rocks)?;
         for rock in rocks {
             for (start, end) in rock.0.iter().tuple_windows() {
                 let direction = (end - start).signum();
                 let mut current = *start;
-                cave[&current] = Material::Rock;
+                grid[&current] = Material::Rock;
                 loop {
                     current += &direction;
-                    cave[&current] = Material::Rock;
+                    grid[&current] = Material::Rock;
                     if &current == end {
                         break;
                     }
                 }
             }
         }
-        cave
+        Ok(Cave { grid, floor })
     }
     fn fill_next_from(&mut self, pos: &Pos<i16>) -> FillResult {
-        let mut x = pos.x - self.offset.x;
-        for y in pos.y..self.grid.size().y - 1 {
+        let mut x = pos.x;
+        for y in pos.y..self.floor {
             x = match [x, x - 1, x + 1].iter().find(|nextx| {
                 let pos = Pos {
                     x: **nextx,
                     y: y + 1,
                 };
-                matches!(
-                    // self.grid[&pos],
-                    unsafe { self.grid.get_unchecked(&pos) }, // this is faster but only safe because the grid is wide enough
-                    Material::Air
-                )
+                // there is only air outside the grid
+                matches!(self.grid.get(&pos), None | Some(Material::Air))
             }) {
                 Some(nextx) => *nextx,
                 None => {
@@ -89,20 +103,38 @@ impl Cave {
                 }
             }
         }
-        FillResult::FloorAtX(x + self.offset.x)
+        FillResult::FloorAtX(x)
     }
 }
 
-impl Index<&Pos<i16>> for Cave {
-    type Output = Material;
-    fn index(&self, index: &Pos<i16>) -> &Self::Output {
-        &self.grid[&(index - &self.offset)]
+impl Cave<OffsetGrid<Material, i16>> {
+    // Sand resting on the rocks stays between them, so only the area around them and the
+    // source is needed. Sand that leaves it falls to the floor.
+    fn around(rocks: &[Rock]) -> Result<Self, AocError> {
+        let points = rocks.iter().flat_map(|rock| rock.0.iter().copied());
+        let grid = OffsetGrid::around(Material::Air, points.chain([SOURCE]), Pos::default())?;
+        Cave::new(rocks, grid)
     }
-}
-
-impl IndexMut<&Pos<i16>> for Cave {
-    fn index_mut(&mut self, index: &Pos<i16>) -> &mut Self::Output {
-        &mut self.grid[&(index - &self.offset)]
+    // The area the sand can reach above the floor, and the rocks.
+    fn with_floor(rocks: &[Rock]) -> Result<Self, AocError> {
+        let floor = floor(rocks)?;
+        let corners = [
+            Pos {
+                x: SOURCE.x - floor,
+                y: floor,
+            },
+            Pos {
+                x: SOURCE.x + floor,
+                y: floor,
+            },
+        ];
+        let points = rocks.iter().flat_map(|rock| rock.0.iter().copied());
+        let grid = OffsetGrid::around(
+            Material::Air,
+            points.chain([SOURCE]).chain(corners),
+            Pos::default(),
+        )?;
+        Cave::new(rocks, grid)
     }
 }
 
@@ -117,14 +149,16 @@ impl Display for Material {
     }
 }
 
-impl Display for Cave {
+impl<G: GridAccess<Material, i16>> Display for Cave<G> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let size = self.grid.size();
-        for y in 0..size.y {
-            for x in 0..size.x {
-                write!(f, "{}", self.grid[&Pos { x, y }])?;
+        if let Some((min, max)) = self.grid.bounds() {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    let material = self.grid.get(&Pos { x, y }).unwrap_or(&Material::Air);
+                    write!(f, "{material}")?;
+                }
+                writeln!(f)?;
             }
-            writeln!(f)?;
         }
         Ok(())
     }
@@ -133,59 +167,51 @@ impl Display for Cave {
 struct Rock(Vec<Pos<i16>>);
 
 impl FromStr for Rock {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut points = Vec::new();
+        let mut points: Vec<Pos<i16>> = Vec::new();
         for point in s.split(" -> ") {
             let (x, y) = point.split_once(',').ok_or("Expected ,")?;
-            points.push(Pos {
+            let point = Pos {
                 x: x.parse()?,
                 y: y.parse()?,
-            });
+            };
+            if point.y < 0 {
+                Err("Rocks must be below the sand source")?;
+            }
+            if let Some(last) = points.last() {
+                if point.x != last.x && point.y != last.y {
+                    Err("Expected a horizontal or vertical line")?;
+                }
+            }
+            points.push(point);
         }
         Ok(Rock(points))
     }
 }
 
-impl FromBufRead for Cave {
-    type Error = Box<dyn Error>;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        let rocks: Vec<Rock> =
-            parse_by_line::<Rock>(br).try_collect::<Result<_, _>, Result<_, _>, _>()??;
-        let minx = rocks
-            .iter()
-            .flat_map(|rock| rock.0.iter().map(|p| p.x))
-            .min()
-            .ok_or("Too few rocks")?;
-        let maxx = rocks
-            .iter()
-            .flat_map(|rock| rock.0.iter().map(|p| p.x))
-            .max()
-            .ok_or("Too few rocks")?;
-        let maxy = rocks
-            .iter()
-            .flat_map(|rock| rock.0.iter().map(|p| p.y))
-            .max()
-            .ok_or("Too few rocks")?;
-        let min = Pos {
-            x: minx - maxy - 10,
-            y: 0,
-        };
-        let max = Pos {
-            x: maxx + maxy + 10,
-            y: maxy + 2,
-        };
-        Ok(Cave::new(&rocks, min, max))
+impl Display for Rock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let points = self.0.iter().map(|p| format!("{},{}", p.x, p.y));
+        write!(f, "{}", points.format(" -> "))
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let mut cave = Cave::read(&mut buf)?;
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let rocks: Vec<Rock> = parse_lines(input_str(input)?).try_collect()?;
+    Cave::around(&rocks)?;
+    let mut out = Vec::new();
+    write_lines(&rocks, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let rocks: Vec<Rock> = parse_lines(input_str(input)?).try_collect()?;
+    let mut cave = Cave::around(&rocks)?;
     for i in 0.. {
-        // println!("{cave}");
-        // sleep(Duration::from_millis(30));
-        match cave.fill_next_from(&Pos { x: 500, y: 0 }) {
-            FillResult::Blocked => Err("Cave filled up")?,
+        trace!(Trace, "Cave after {i} units of sand:\n{cave}");
+        match cave.fill_next_from(&SOURCE) {
+            FillResult::Blocked => Err(AocError::no_solution("the cave filled up"))?,
             FillResult::FloorAtX(_) => return Ok(i),
             FillResult::Filled => {}
         }
@@ -193,19 +219,110 @@ pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     unreachable!()
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let mut cave = Cave::read(&mut buf)?;
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
+    let rocks: Vec<Rock> = parse_lines(input_str(input)?).try_collect()?;
+    let mut cave = Cave::with_floor(&rocks)?;
     for i in 1.. {
-        // println!("{cave}");
-        // sleep(Duration::from_millis(1));
-        match cave.fill_next_from(&Pos { x: 500, y: 0 }) {
+        trace!(Trace, "Cave after {} units of sand:\n{cave}", i - 1);
+        match cave.fill_next_from(&SOURCE) {
             FillResult::Blocked => return Ok(i),
             FillResult::FloorAtX(x) => {
-                let y = cave.grid.size().y - 1;
-                cave[&Pos { x, y }] = Material::Sand;
+                let y = cave.floor;
+                cave.grid[&Pos { x, y }] = Material::Sand;
             }
             FillResult::Filled => {}
         }
     }
     unreachable!()
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // The rocks stay below a depth equal to their half width, so the sand always flows past
+    // them instead of blocking the source. The limit keeps all coordinates within i16.
+    let size = size.clamp(1, 3000) as i64;
+    let half_width = 10 + size;
+    let depth = 10 + size;
+    for _ in 0..size {
+        let mut point = (
+            500 + rng.range(-half_width..=half_width),
+            half_width + 2 + rng.range(0..=depth),
+        );
+        let mut points = vec![format!("{},{}", point.0, point.1)];
+        for i in 0..rng.range(1..=5) {
+            let step = rng.range(1..=6);
+            let (value, range) = if i % 2 == 0 {
+                (&mut point.0, 500 - half_width..=500 + half_width)
+            } else {
+                (&mut point.1, half_width + 2..=half_width + 2 + depth)
+            };
+            // turn around at the border, the ranges are wider than a step
+            if rng.ratio(1, 2) && range.contains(&(*value + step)) {
+                *value += step;
+            } else if range.contains(&(*value - step)) {
+                *value -= step;
+            } else {
+                *value += step;
+            }
+            points.push(format!("{},{}", point.0, point.1));
+        }
+        writeln!(out, "{}", points.join(" -> "))?;
+    }
+    Ok(())
+}
+
+fn reference_sand(buf: impl BufRead, with_floor: bool) -> Result<usize, AocError> {
+    let mut blocked = HashSet::new();
+    for line in buf.lines() {
+        let line = line?;
+        let mut points = Vec::new();
+        for point in line.split(" -> ") {
+            let (x, y) = point.split_once(',').ok_or("Expected ,")?;
+            points.push((x.parse::<i32>()?, y.parse::<i32>()?));
+        }
+        for pair in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+            for x in x1.min(x2)..=x1.max(x2) {
+                for y in y1.min(y2)..=y1.max(y2) {
+                    blocked.insert((x, y));
+                }
+            }
+        }
+    }
+    let max_y = blocked.iter().map(|p| p.1).max().ok_or("Too few rocks")?;
+    let floor = max_y + 2;
+    for grains in 0.. {
+        let (mut x, mut y) = (500, 0);
+        loop {
+            if !with_floor && y > max_y {
+                // this grain falls into the abyss
+                return Ok(grains);
+            }
+            match [x, x - 1, x + 1]
+                .into_iter()
+                .find(|nx| y + 1 < floor && !blocked.contains(&(*nx, y + 1)))
+            {
+                Some(nx) => {
+                    x = nx;
+                    y += 1;
+                }
+                None => break,
+            }
+        }
+        blocked.insert((x, y));
+        if (x, y) == (500, 0) {
+            if with_floor {
+                return Ok(grains + 1);
+            }
+            Err(AocError::no_solution("the cave filled up"))?;
+        }
+    }
+    unreachable!()
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_sand(buf, false)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    reference_sand(buf, true)
+}
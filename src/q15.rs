@@ -1,8 +1,19 @@
-use lazy_static::lazy_static;
-use regex::Regex;
-use std::{error::Error, io::BufRead, ops::Range, str::FromStr};
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    ops::Range,
+    str::FromStr,
+};
 
-use crate::{parsing::parse_by_line, pos::Pos, regex_parse};
+use crate::{
+    context::Context,
+    error::AocError,
+    grid::{DIAGONALS, SIDES},
+    parsing::{input_str, parse_lines_parallel, write_lines},
+    pos::Pos,
+    random::Rng,
+    scan, trace,
+};
 
 struct Sensor {
     center: Pos<i32>,
@@ -11,14 +22,14 @@ struct Sensor {
 }
 
 impl Sensor {
-    pub fn new(center: Pos<i32>, beacon: Pos<i32>) -> Sensor {
-        let dist = &beacon - &center;
-        let manhattan = dist.x.abs() + dist.y.abs();
-        Sensor {
+    pub fn new(center: Pos<i32>, beacon: Pos<i32>) -> Result<Sensor, AocError> {
+        let distance = |c: i32, b: i32| (b as i64 - c as i64).abs();
+        let manhattan = distance(center.x, beacon.x) + distance(center.y, beacon.y);
+        Ok(Sensor {
             center,
             beacon,
-            manhattan,
-        }
+            manhattan: i32::try_from(manhattan)?,
+        })
     }
     fn range_at(&self, y: i32) -> Option<Range<i32>> {
         let remaining = self.manhattan - (y - self.center.y).abs();
@@ -31,22 +42,28 @@ impl Sensor {
 }
 
 impl FromStr for Sensor {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Sensor at x=2, y=18: closest beacon is at x=-2, y=15
-        lazy_static! {
-            static ref REGEX: Regex = Regex::new(
-                r"^Sensor at x=(-?\d+), y=(-?\d+): closest beacon is at x=(-?\d+), y=(-?\d+)$"
-            )
-            .unwrap();
-        }
-        match regex_parse!(REGEX, s, (i32, i32, i32, i32)) {
-            None => Err("Sensor could not be parsed".into()),
-            Some(Err(e)) => Err(e),
-            Some(Ok((sx, sy, bx, by))) => {
-                Ok(Sensor::new(Pos { x: sx, y: sy }, Pos { x: bx, y: by }))
-            }
-        }
+        let (sx, sy, bx, by) = scan!(
+            s,
+            "Sensor at x={}, y={}: closest beacon is at x={}, y={}",
+            i32,
+            i32,
+            i32,
+            i32
+        )?;
+        Sensor::new(Pos { x: sx, y: sy }, Pos { x: bx, y: by })
+    }
+}
+
+impl Display for Sensor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (center, beacon) = (&self.center, &self.beacon);
+        write!(
+            f,
+            "Sensor at x={}, y={}: closest beacon is at x={}, y={}",
+            center.x, center.y, beacon.x, beacon.y
+        )
     }
 }
 
@@ -113,8 +130,15 @@ fn range_and(lhs: &Range<i32>, rhs: &Range<i32>) -> Option<Range<i32>> {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let sensors = parse_by_line::<Sensor>(buf).collect::<Result<Result<Vec<Sensor>, _>, _>>()??;
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let sensors: Vec<Sensor> = parse_lines_parallel(input_str(input)?)?;
+    let mut out = Vec::new();
+    write_lines(&sensors, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let sensors: Vec<Sensor> = parse_lines_parallel(input_str(input)?)?;
     let y = 2_000_000;
     let mut ranges = Ranges::new();
     ranges.reset_from_iter(sensors.iter().filter_map(|sensor| sensor.range_at(y)));
@@ -126,13 +150,16 @@ pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     Ok(ranges.count())
 }
 
-pub fn b(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
-    let mut sensors =
-        parse_by_line::<Sensor>(buf).collect::<Result<Result<Vec<Sensor>, _>, _>>()??;
+pub fn b(input: &[u8], ctx: &Context) -> Result<i64, AocError> {
+    let mut sensors: Vec<Sensor> = parse_lines_parallel(input_str(input)?)?;
     sensors.sort_by_key(|s| s.center.x);
     let valid_range = 0..4_000_001;
     let mut occupied = Ranges::new();
     for y in valid_range.clone() {
+        if y % 4096 == 0 {
+            ctx.check()?;
+            ctx.set_progress(y as f64 / valid_range.len() as f64);
+        }
         occupied.reset_from_iter(
             sensors
                 .iter()
@@ -142,10 +169,111 @@ pub fn b(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
             0 => {}
             1 => return Ok((occupied.0[0].end as i64) * 4_000_000 + y as i64),
             _ => {
-                println!("{:?}", occupied.0);
-                Err("Found row with multiple free spaces")?
+                trace!(Debug, "Free spaces in row {y}: {:?}", occupied.0);
+                Err(AocError::no_solution("a row has multiple free spaces"))?
+            }
+        }
+    }
+    Err(AocError::no_solution("no free space found"))
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // b needs exactly one uncovered position in the search area. Four sensors beside it
+    // cover everything except its diagonals, and four sensors beyond the corners cover
+    // those. All other sensors stay just out of reach of it.
+    const MAX: i64 = 4_000_000;
+    let (px, py) = (rng.range(1..=MAX - 1), rng.range(0..=MAX));
+    let mut sensors = Vec::new();
+    for (steps, distance) in [(SIDES, 2 * MAX), (DIAGONALS, MAX)] {
+        for step in steps {
+            let (dx, dy) = (i64::from(step.x), i64::from(step.y));
+            sensors.push(((px + dx * distance, py + dy * distance), 2 * MAX - 1));
+        }
+    }
+    while sensors.len() < size {
+        let (sx, sy) = (rng.range(0..=MAX), rng.range(0..=MAX));
+        let distance = (sx - px).abs() + (sy - py).abs();
+        if distance > 1 {
+            sensors.push(((sx, sy), rng.range(1..=distance - 1)));
+        }
+    }
+    rng.shuffle(&mut sensors);
+    for ((sx, sy), manhattan) in sensors {
+        let bx = rng.range(-manhattan..=manhattan);
+        let by = (manhattan - bx.abs()) * if rng.ratio(1, 2) { 1 } else { -1 };
+        writeln!(
+            out,
+            "Sensor at x={sx}, y={sy}: closest beacon is at x={}, y={}",
+            sx + bx,
+            sy + by
+        )?;
+    }
+    Ok(())
+}
+
+struct ReferenceSensor {
+    x: i64,
+    y: i64,
+    beacon: (i64, i64),
+    range: i64,
+}
+
+impl ReferenceSensor {
+    fn covers(&self, x: i64, y: i64) -> bool {
+        (self.x - x).abs() + (self.y - y).abs() <= self.range
+    }
+}
+
+fn reference_sensors(buf: impl BufRead) -> Result<Vec<ReferenceSensor>, AocError> {
+    let mut sensors = Vec::new();
+    for line in buf.lines() {
+        let line = line?;
+        let numbers = line
+            .split(|c: char| !c.is_ascii_digit() && c != '-')
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, bx, by] = numbers[..] else {
+            Err("Sensor could not be parsed")?
+        };
+        sensors.push(ReferenceSensor {
+            x,
+            y,
+            beacon: (bx, by),
+            range: (bx - x).abs() + (by - y).abs(),
+        });
+    }
+    Ok(sensors)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let sensors = reference_sensors(buf)?;
+    let y = 2_000_000;
+    let min_x = sensors.iter().map(|s| s.x - s.range).min().unwrap_or(0);
+    let max_x = sensors.iter().map(|s| s.x + s.range).max().unwrap_or(0);
+    Ok((min_x..=max_x)
+        .filter(|x| {
+            sensors.iter().any(|s| s.covers(*x, y)) && sensors.iter().all(|s| s.beacon != (*x, y))
+        })
+        .count())
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i64, AocError> {
+    // the only free position has a covered neighbour, so it lies just outside a sensor's range
+    let sensors = reference_sensors(buf)?;
+    let area = 0..=4_000_000;
+    for sensor in sensors.iter() {
+        let distance = sensor.range + 1;
+        let min_x = (sensor.x - distance).max(*area.start());
+        let max_x = (sensor.x + distance).min(*area.end());
+        for x in min_x..=max_x {
+            let dy = distance - (sensor.x - x).abs();
+            for y in [sensor.y - dy, sensor.y + dy] {
+                if area.contains(&y) && !sensors.iter().any(|s| s.covers(x, y)) {
+                    return Ok(x * 4_000_000 + y);
+                }
             }
         }
     }
-    Err("Did not find solution".into())
+    Err(AocError::no_solution("no free space found"))
 }
@@ -1,17 +1,22 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
-    error::Error,
-    io::BufRead,
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    fmt::Display,
+    io::{BufRead, Write},
     str::FromStr,
 };
 
 use bitvec::prelude::BitArray;
+use itertools::Itertools;
 use lazy_static::lazy_static;
 use regex::Regex;
 
 use crate::{
+    context::Context,
+    error::AocError,
     optimize::{optimize, Optimize},
-    parsing::parse_by_line,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+    regex_parse,
 };
 
 #[derive(Clone)]
@@ -21,25 +26,46 @@ struct Valve {
     destination_names: Vec<String>,
 }
 
+struct ValveLine<'a> {
+    name: &'a str,
+    flow_rate: i32,
+    destinations: &'a str,
+}
+
 impl FromStr for Valve {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         lazy_static! {
-            static ref REGEX: Regex = Regex::new(r"^Valve ([A-Z][A-Z]) has flow rate=(\d+); tunnels? leads? to valves? ([A-Z][A-Z].*)$").unwrap();
+            static ref REGEX: Regex = Regex::new(r"^Valve (?P<name>[A-Z][A-Z]) has flow rate=(?P<flow_rate>\d+); tunnels? leads? to valves? (?P<destinations>[A-Z][A-Z].*)$").unwrap();
         }
-        match REGEX.captures(s) {
-            None => Err("line did not match pattern")?,
-            Some(captures) => {
-                let name = captures.get(1).unwrap().as_str().to_string();
-                let flow_rate = captures.get(2).unwrap().as_str().parse()?;
-                let destinations = captures.get(3).unwrap().as_str();
-                let destination_names = destinations.split(", ").map(|d| d.to_string()).collect();
-                Ok(Valve {
-                    name,
-                    flow_rate,
-                    destination_names,
-                })
+        let line = regex_parse!(
+            REGEX,
+            s,
+            ValveLine {
+                name: &str,
+                flow_rate: i32,
+                destinations: &str
             }
+        )
+        .ok_or("line did not match pattern")??;
+        Ok(Valve {
+            name: line.name.to_string(),
+            flow_rate: line.flow_rate,
+            destination_names: line
+                .destinations
+                .split(", ")
+                .map(|d| d.to_string())
+                .collect(),
+        })
+    }
+}
+
+impl Display for Valve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Valve {} has flow rate={}; ", self.name, self.flow_rate)?;
+        match &self.destination_names[..] {
+            [destination] => write!(f, "tunnel leads to valve {destination}"),
+            destinations => write!(f, "tunnels lead to valves {}", destinations.join(", ")),
         }
     }
 }
@@ -62,6 +88,29 @@ struct Problem {
     shortest_paths: Vec<Vec<i32>>,
 }
 
+impl Problem {
+    fn open_next(&self, state: &State, runner: usize, next: &mut Vec<State>) {
+        for v in state.unopened_valves.iter_ones() {
+            let mut runners = state.runners.clone();
+            let runner = &mut runners[runner];
+            runner.time_left -= self.shortest_paths[runner.position][v] + 1;
+            if runner.time_left < 0 {
+                continue;
+            }
+            runner.position = v;
+            let projected_release =
+                state.projected_release + runner.time_left * self.valves[v].flow_rate;
+            let mut unopened_valves = state.unopened_valves;
+            unopened_valves.set(v, false);
+            next.push(State {
+                runners,
+                projected_release,
+                unopened_valves,
+            });
+        }
+    }
+}
+
 impl Optimize for Problem {
     type State = State;
     type StateValue = i32;
@@ -73,47 +122,43 @@ impl Optimize for Problem {
     fn potential(&self, state: &Self::State) -> Self::StateValue {
         let mut potential_release = state.projected_release;
         // calculate upper bound for potential release by assuming that every valve can be reached in 1 step
-        // this assumes that they are sorted in reverse already
+        // and opened in the next, the largest flow rates first (the valves are sorted by name)
+        let mut flow_rates: Vec<i32> = state
+            .unopened_valves
+            .iter_ones()
+            .map(|v| self.valves[v].flow_rate)
+            .collect();
+        flow_rates.sort_by(|a, b| b.cmp(a));
         let mut time_left = [state.runners[0].time_left, state.runners[1].time_left];
-        for v in state.unopened_valves.iter_ones() {
+        for flow_rate in flow_rates {
             let tl = if time_left[0] > time_left[1] {
-                time_left[0] -= 1;
+                time_left[0] -= 2;
                 time_left[0]
             } else {
-                time_left[1] -= 1;
+                time_left[1] -= 2;
                 time_left[1]
             };
-            match tl {
-                -1 => break,
-                0 => {}
-                t => potential_release += t * self.valves[v].flow_rate,
+            if tl <= 0 {
+                break;
             }
+            potential_release += tl * flow_rate;
         }
         potential_release
     }
     fn next_states(&self, state: &State, next: &mut Vec<State>) {
-        next.extend(
-            state
-                .unopened_valves
-                .iter_ones()
-                .map(|v| {
-                    let mut runners = state.runners.clone();
-                    let runner = runners.iter_mut().max_by_key(|r| r.time_left).unwrap(); // unwrap will not fail, there are two
-                    let duration = self.shortest_paths[runner.position][v];
-                    runner.time_left = runner.time_left - duration - 1;
-                    runner.position = v;
-                    let projected_release =
-                        state.projected_release + runner.time_left * self.valves[v].flow_rate;
-                    let mut unopened_valves = state.unopened_valves;
-                    unopened_valves.set(v, false);
-                    State {
-                        runners,
-                        projected_release,
-                        unopened_valves,
-                    }
-                })
-                .filter(|s| s.runners.iter().all(|r| r.time_left >= 0)),
-        );
+        // the runner with the most time left opens the next valve
+        let first = if state.runners[0].time_left > state.runners[1].time_left {
+            0
+        } else {
+            1
+        };
+        self.open_next(state, first, next);
+        // or it stops and leaves the remaining valves to the other one
+        if state.runners[1 - first].time_left > 0 {
+            let mut stopped = state.clone();
+            stopped.runners[first].time_left = 0;
+            self.open_next(&stopped, 1 - first, next);
+        }
     }
     fn add_if_improvement(&self, solutions: &mut Self::Solutions, state: &Self::State) -> bool {
         let key = (
@@ -130,18 +175,24 @@ impl Optimize for Problem {
                 true
             }
             Entry::Occupied(mut v) => {
-                if v.get().iter().any(|s| s > &value) {
+                // an earlier state with as much time for both and as much release is as good
+                let dominated = v.get().iter().any(|(time_left, release)| {
+                    time_left[0] >= value.0[0] && time_left[1] >= value.0[1] && *release >= value.1
+                });
+                if !dominated {
                     v.get_mut().push(value);
-                    false
-                } else {
-                    true
                 }
+                !dominated
             }
         }
     }
 }
 
-fn solve_for_most_pressure(valves: Vec<Valve>, time_left: [i32; 2]) -> Result<i32, Box<dyn Error>> {
+fn solve_for_most_pressure(
+    valves: Vec<Valve>,
+    time_left: [i32; 2],
+    ctx: &Context,
+) -> Result<i32, AocError> {
     const UNREACHABLE: i32 = 1000000;
     let mut shortest_paths = vec![vec![UNREACHABLE; valves.len()]; valves.len()];
     for (number, valve) in valves.iter().enumerate() {
@@ -187,17 +238,189 @@ fn solve_for_most_pressure(valves: Vec<Valve>, time_left: [i32; 2]) -> Result<i3
         projected_release: 0,
         unopened_valves,
     };
-    Ok(optimize(&problem, initial).projected_release)
+    Ok(optimize(&problem, initial, ctx)?.projected_release)
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let valves: Vec<Valve> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&valves, &mut out)?;
+    Ok(out)
 }
 
-pub fn a(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
-    let mut valves = parse_by_line::<Valve>(buf).collect::<Result<Result<Vec<_>, _>, _>>()??;
+pub fn a(input: &[u8], ctx: &Context) -> Result<i32, AocError> {
+    let mut valves = parse_lines::<Valve>(input_str(input)?).collect::<Result<Vec<_>, _>>()?;
     valves.sort_by_key(|v| v.name.clone());
-    solve_for_most_pressure(valves, [30, 0])
+    solve_for_most_pressure(valves, [30, 0], ctx)
 }
 
-pub fn b(buf: impl BufRead) -> Result<i32, Box<dyn Error>> {
-    let mut valves = parse_by_line::<Valve>(buf).collect::<Result<Result<Vec<_>, _>, _>>()??;
+pub fn b(input: &[u8], ctx: &Context) -> Result<i32, AocError> {
+    let mut valves = parse_lines::<Valve>(input_str(input)?).collect::<Result<Vec<_>, _>>()?;
     valves.sort_by_key(|v| v.name.clone());
-    solve_for_most_pressure(valves, [26, 26])
+    solve_for_most_pressure(valves, [26, 26], ctx)
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // names are two letters and AA, the start, must sort first. The unopened valves are a
+    // 64 bit set indexed by sorted name, and like in the real input only a few valves have
+    // a flow rate.
+    let size = size.clamp(2, 26 * 26);
+    let mut names: Vec<String> = (0..26 * 26)
+        .map(|i: usize| {
+            format!(
+                "{}{}",
+                (b'A' + (i / 26) as u8) as char,
+                (b'A' + (i % 26) as u8) as char
+            )
+        })
+        .collect();
+    rng.shuffle(&mut names[1..]);
+    names.truncate(size);
+    names.sort();
+    let mut tunnels = vec![Vec::new(); size];
+    for valve in 1..size {
+        let other = rng.below(valve);
+        tunnels[valve].push(other);
+        tunnels[other].push(valve);
+    }
+    for _ in 0..size / 4 {
+        let (v1, v2) = (rng.below(size), rng.below(size));
+        if v1 != v2 && !tunnels[v1].contains(&v2) {
+            tunnels[v1].push(v2);
+            tunnels[v2].push(v1);
+        }
+    }
+    let mut flow_rates = vec![0; size];
+    let mut with_flow: Vec<usize> = (1..size.min(64)).collect();
+    rng.shuffle(&mut with_flow);
+    for valve in with_flow.into_iter().take(15) {
+        flow_rates[valve] = rng.range(1..=25);
+    }
+    let mut order: Vec<usize> = (0..size).collect();
+    rng.shuffle(&mut order);
+    for valve in order {
+        let destinations = tunnels[valve]
+            .iter()
+            .map(|t| names[*t].as_str())
+            .collect::<Vec<_>>();
+        let s = if destinations.len() == 1 { "" } else { "s" };
+        writeln!(
+            out,
+            "Valve {} has flow rate={}; tunnel{s} lead{} to valve{s} {}",
+            names[valve],
+            flow_rates[valve],
+            if s.is_empty() { "s" } else { "" },
+            destinations.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+struct ReferenceValves {
+    flow_rates: Vec<i32>,
+    // the shortest distance between every two valves
+    distances: Vec<Vec<i32>>,
+    start: usize,
+}
+
+fn reference_valves(buf: impl BufRead) -> Result<ReferenceValves, AocError> {
+    let mut names = Vec::new();
+    let mut flow_rates = Vec::new();
+    let mut tunnels = Vec::new();
+    for line in buf.lines() {
+        let line = line?;
+        let words: Vec<&str> = line.split(' ').collect();
+        let [_, name, _, _, rate, ..] = words[..] else {
+            Err("line did not match pattern")?
+        };
+        let rate = rate.strip_prefix("rate=").ok_or("Expected rate")?;
+        let (_, destinations) = line.split_once(" to valve").ok_or("Expected valves")?;
+        names.push(name.to_string());
+        flow_rates.push(rate.trim_end_matches(';').parse()?);
+        tunnels.push(
+            destinations
+                .trim_start_matches('s')
+                .trim()
+                .split(", ")
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+    let mut distances = Vec::new();
+    for from in 0..names.len() {
+        let mut distance = vec![i32::MAX; names.len()];
+        distance[from] = 0;
+        let mut queue = VecDeque::from([from]);
+        while let Some(valve) = queue.pop_front() {
+            for destination in tunnels[valve].iter() {
+                let next = names
+                    .iter()
+                    .position(|n| n == destination)
+                    .ok_or("Unknown valve")?;
+                if distance[next] == i32::MAX {
+                    distance[next] = distance[valve] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        distances.push(distance);
+    }
+    let start = names.iter().position(|n| n == "AA").ok_or("No valve AA")?;
+    Ok(ReferenceValves {
+        flow_rates,
+        distances,
+        start,
+    })
+}
+
+impl ReferenceValves {
+    // tries every order of opening valves and records the best release for every set of
+    // opened valves
+    fn visit(
+        &self,
+        position: usize,
+        time_left: i32,
+        opened: Vec<usize>,
+        released: i32,
+        best: &mut HashMap<Vec<usize>, i32>,
+    ) {
+        let mut key = opened.clone();
+        key.sort();
+        let entry = best.entry(key).or_insert(0);
+        *entry = released.max(*entry);
+        for valve in 0..self.flow_rates.len() {
+            let time_left = time_left.saturating_sub(self.distances[position][valve]) - 1;
+            if self.flow_rates[valve] > 0 && !opened.contains(&valve) && time_left > 0 {
+                let mut opened = opened.clone();
+                opened.push(valve);
+                let released = released + time_left * self.flow_rates[valve];
+                self.visit(valve, time_left, opened, released, best);
+            }
+        }
+    }
+    fn best_per_opened(&self, time_left: i32) -> HashMap<Vec<usize>, i32> {
+        let mut best = HashMap::new();
+        self.visit(self.start, time_left, Vec::new(), 0, &mut best);
+        best
+    }
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i32, AocError> {
+    let valves = reference_valves(buf)?;
+    Ok(valves.best_per_opened(30).into_values().max().unwrap_or(0))
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i32, AocError> {
+    // you and the elephant open different valves
+    let valves = reference_valves(buf)?;
+    let best = valves.best_per_opened(26);
+    let mut most = 0;
+    for (yours, your_release) in best.iter() {
+        for (elephants, elephant_release) in best.iter() {
+            if yours.iter().all(|v| !elephants.contains(v)) {
+                most = most.max(your_release + elephant_release);
+            }
+        }
+    }
+    Ok(most)
 }
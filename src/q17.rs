@@ -1,8 +1,17 @@
-use std::{collections::VecDeque, error::Error, fmt::Display, io::BufRead};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    fmt::Display,
+    io::{BufRead, Write},
+};
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    parsing::{FromBufRead, ToBufWrite},
+    random::Rng,
+    trace,
+};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 struct Row(u8);
 
 #[derive(Clone)]
@@ -91,7 +100,7 @@ impl Shape {
 }
 
 impl FromBufRead for JetDirection {
-    type Error = Box<dyn Error>;
+    type Error = AocError;
     fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
         let mut buf = [0u8; 1];
         if br.read(&mut buf)? != 1 {
@@ -105,6 +114,15 @@ impl FromBufRead for JetDirection {
     }
 }
 
+impl ToBufWrite for JetDirection {
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            JetDirection::Left => bw.write_all(b"<"),
+            JetDirection::Right => bw.write_all(b">"),
+        }
+    }
+}
+
 impl Grid {
     fn new() -> Grid {
         Grid {
@@ -126,6 +144,31 @@ impl Grid {
         }
         true
     }
+    // The empty cells of every row that can be reached from the top by moving left, right and
+    // down, from the top row until a row without any. Rocks can never come anywhere else, so
+    // chambers with the same reachable air, next rock and next jet will grow in the same way.
+    // Like in the reference, air deeper than 1000 rows is treated as sealed.
+    fn reachable_air(&self) -> Vec<Row> {
+        let mut air = Vec::new();
+        let mut above = 0b1111111;
+        for row in self.rows.iter().take(1000) {
+            let empty = !row.0 & 0b1111111;
+            let mut reachable = above & empty;
+            loop {
+                let spread = (reachable | reachable << 1 | reachable >> 1) & empty;
+                if spread == reachable {
+                    break;
+                }
+                reachable = spread;
+            }
+            if reachable == 0 {
+                break;
+            }
+            air.push(Row(reachable));
+            above = reachable;
+        }
+        air
+    }
     fn add(&mut self, shape: &Shape, mut y: i16) {
         for (i, row) in shape.rows.iter().enumerate() {
             if y >= i as i16 {
@@ -153,36 +196,33 @@ impl Chamber {
         loop {
             if self.grid.would_fit(&shape, y + 1) {
                 y += 1;
-                // let mut c = self.clone();
-                // c.grid.add(&shape, y);
-                // println!("Shifted down");
-                // println!("{c}");
+                trace!(Trace, "Shifted down\n{}", self.with_shape(&shape, y));
             } else {
                 break;
             }
             let index = self.jet_index;
             self.jet_index = (self.jet_index + 1) % self.jets.len();
             let new_shape = match self.jets[index] {
-                JetDirection::Left => {
-                    // println!("Shifted L");
-                    shape.try_shl()
-                }
-                JetDirection::Right => {
-                    // println!("Shifted R");
-                    shape.try_shr()
-                }
+                JetDirection::Left => shape.try_shl(),
+                JetDirection::Right => shape.try_shr(),
             };
             if self.grid.would_fit(&new_shape, y) {
                 shape = new_shape;
-                // println!("Shifted LR");
-                // let mut c = self.clone();
-                // c.grid.add(&shape, y);
-                // println!("{c}");
+                trace!(
+                    Trace,
+                    "Pushed by jet {index}\n{}",
+                    self.with_shape(&shape, y)
+                );
             }
         }
         self.grid.add(&shape, y);
-        // println!("Done");
-        // println!("{}", self);
+        trace!(Debug, "Rock {} came to rest\n{self}", self.num_rocks);
+    }
+    // a copy of the chamber with a falling shape drawn in, for tracing
+    fn with_shape(&self, shape: &Shape, y: i16) -> Chamber {
+        let mut chamber = self.clone();
+        chamber.grid.add(shape, y);
+        chamber
     }
     fn height(&self) -> usize {
         self.grid.height()
@@ -198,8 +238,21 @@ impl Display for Chamber {
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let jets = JetDirection::read_iter(&mut buf).collect::<Result<Vec<JetDirection>, _>>()?;
+// the jets are the first line, without its newline
+fn read_jets(buf: &mut impl BufRead) -> Result<Vec<JetDirection>, AocError> {
+    let line = buf.lines().next().ok_or("Expected jets")??;
+    JetDirection::read_iter(&mut line.as_bytes()).collect()
+}
+
+pub fn reformat(mut input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let mut out = Vec::new();
+    read_jets(&mut input)?.write(&mut out)?;
+    writeln!(out)?;
+    Ok(out)
+}
+
+pub fn a(mut input: &[u8]) -> Result<usize, AocError> {
+    let jets = read_jets(&mut input)?;
     let mut chamber = Chamber::new(jets);
     let shapes = Shape::get_all();
     let mut shape_iter = shapes.iter().cycle();
@@ -210,24 +263,163 @@ pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
     Ok(chamber.height())
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
+pub fn b(mut input: &[u8]) -> Result<usize, AocError> {
     let num_rocks = 1000000000000usize;
-    let jets = JetDirection::read_iter(&mut buf).collect::<Result<Vec<JetDirection>, _>>()?;
+    let jets = read_jets(&mut input)?;
     let shapes = Shape::get_all();
-    // println!("{}", jets.len());
-    let repeat_cycle = 1740; //jets.len() * shapes.len() * 4;
-    let repeat_initial = num_rocks % repeat_cycle;
+    trace!(Info, "{} jets", jets.len());
     let mut chamber = Chamber::new(jets);
-    let mut shape_iter = shapes.iter().cycle();
-    for _ in 0..repeat_initial {
-        let shape = shape_iter.next().unwrap(); // cannot fail due to cycle
-        chamber.add(shape.clone());
+    // the number of rocks and the height for every state the chamber was in
+    let mut seen = HashMap::new();
+    while chamber.num_rocks < num_rocks {
+        let shape = chamber.num_rocks % shapes.len();
+        let state = (shape, chamber.jet_index, chamber.grid.reachable_air());
+        if let Some((rocks, height)) = seen.insert(state, (chamber.num_rocks, chamber.height())) {
+            // from here the chamber repeats its growth since then, so skip whole cycles
+            let cycle = chamber.num_rocks - rocks;
+            let cycles = (num_rocks - chamber.num_rocks) / cycle;
+            let skipped_height = cycles * (chamber.height() - height);
+            let remaining = (num_rocks - chamber.num_rocks) % cycle;
+            for shape in shapes.iter().cycle().skip(shape).take(remaining) {
+                chamber.add(shape.clone());
+            }
+            trace!(Info, "Skipped {cycles} cycles of {cycle} rocks");
+            return Ok(chamber.height() + skipped_height);
+        }
+        chamber.add(shapes[shape].clone());
     }
-    let initial_height = chamber.height();
-    for _ in 0..repeat_cycle {
-        let shape = shape_iter.next().unwrap(); // cannot fail due to cycle
-        chamber.add(shape.clone());
+    Ok(chamber.height())
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    let jets: Vec<u8> = (0..size.max(1)).map(|_| *rng.choose(b"<>")).collect();
+    out.write_all(&jets)?;
+    writeln!(out)
+}
+
+// the cells of every rock, relative to its bottom left corner
+const REFERENCE_ROCKS: [&[(i64, i64)]; 5] = [
+    &[(0, 0), (1, 0), (2, 0), (3, 0)],
+    &[(1, 0), (0, 1), (1, 1), (2, 1), (1, 2)],
+    &[(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)],
+    &[(0, 0), (0, 1), (0, 2), (0, 3)],
+    &[(0, 0), (1, 0), (0, 1), (1, 1)],
+];
+
+struct ReferenceChamber {
+    jets: Vec<i64>,
+    jet: usize,
+    rocks: usize,
+    occupied: HashSet<(i64, i64)>,
+    height: i64,
+}
+
+impl ReferenceChamber {
+    fn read(buf: impl BufRead) -> Result<ReferenceChamber, AocError> {
+        let jets = buf
+            .bytes()
+            .take_while(|b| !matches!(b, Ok(b'\n')))
+            .map(|b| match b? {
+                b'<' => Ok(-1),
+                b'>' => Ok(1),
+                _ => Err("Unexpected JetDirection".into()),
+            })
+            .collect::<Result<Vec<_>, AocError>>()?;
+        if jets.is_empty() {
+            Err("No jets")?;
+        }
+        Ok(ReferenceChamber {
+            jets,
+            jet: 0,
+            rocks: 0,
+            occupied: HashSet::new(),
+            height: 0,
+        })
+    }
+    fn is_free(&self, x: i64, y: i64) -> bool {
+        (0..7).contains(&x) && y >= 0 && !self.occupied.contains(&(x, y))
+    }
+    fn fits(&self, rock: &[(i64, i64)], x: i64, y: i64) -> bool {
+        rock.iter().all(|(dx, dy)| self.is_free(x + dx, y + dy))
+    }
+    fn drop_rock(&mut self) {
+        let rock = REFERENCE_ROCKS[self.rocks % REFERENCE_ROCKS.len()];
+        self.rocks += 1;
+        let (mut x, mut y) = (2, self.height + 3);
+        loop {
+            let pushed = x + self.jets[self.jet];
+            self.jet = (self.jet + 1) % self.jets.len();
+            if self.fits(rock, pushed, y) {
+                x = pushed;
+            }
+            if !self.fits(rock, x, y - 1) {
+                break;
+            }
+            y -= 1;
+        }
+        for (dx, dy) in rock.iter() {
+            self.occupied.insert((x + dx, y + dy));
+            self.height = self.height.max(y + dy + 1);
+        }
+    }
+    // The empty cells that can be reached from the top by moving left, right and down,
+    // relative to the height. Rocks can never come anywhere else, so chambers with the same
+    // reachable air, next rock and next jet will grow in the same way.
+    // air deeper than 1000 below the top is treated as sealed, no rock falls that far
+    fn reachable_air(&self) -> Vec<(i64, i64)> {
+        let mut reachable: HashSet<(i64, i64)> = (0..7).map(|x| (x, self.height)).collect();
+        let mut queue: Vec<(i64, i64)> = reachable.iter().copied().collect();
+        while let Some((x, y)) = queue.pop() {
+            for next in [(x - 1, y), (x + 1, y), (x, y - 1)] {
+                if self.height - next.1 <= 1000
+                    && self.is_free(next.0, next.1)
+                    && reachable.insert(next)
+                {
+                    queue.push(next);
+                }
+            }
+        }
+        let mut reachable: Vec<_> = reachable
+            .into_iter()
+            .map(|(x, y)| (x, self.height - y))
+            .collect();
+        reachable.sort();
+        reachable
+    }
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i64, AocError> {
+    let mut chamber = ReferenceChamber::read(buf)?;
+    for _ in 0..2022 {
+        chamber.drop_rock();
+    }
+    Ok(chamber.height)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i64, AocError> {
+    let num_rocks = 1000000000000usize;
+    let mut chamber = ReferenceChamber::read(buf)?;
+    let mut seen = HashMap::new();
+    let mut skipped_height = None;
+    while chamber.rocks < num_rocks {
+        if skipped_height.is_none() {
+            let air = chamber.reachable_air();
+            let key = (chamber.rocks % REFERENCE_ROCKS.len(), chamber.jet, air);
+            match seen.entry(key) {
+                Entry::Vacant(e) => {
+                    e.insert((chamber.rocks, chamber.height));
+                }
+                Entry::Occupied(e) => {
+                    let (rocks, height) = e.get();
+                    let cycle = chamber.rocks - rocks;
+                    let cycles = (num_rocks - chamber.rocks) / cycle;
+                    skipped_height = Some(cycles as i64 * (chamber.height - height));
+                    chamber.rocks += cycles * cycle;
+                    continue;
+                }
+            }
+        }
+        chamber.drop_rock();
     }
-    let cycle_height = chamber.height() - initial_height;
-    Ok(initial_height + cycle_height * (num_rocks / repeat_cycle))
+    Ok(chamber.height + skipped_height.unwrap_or(0))
 }
@@ -1,13 +1,17 @@
 use itertools::{Itertools, MinMaxResult};
 use std::{
     collections::HashSet,
-    error::Error,
-    io::BufRead,
+    fmt::Display,
+    io::{BufRead, Write},
     ops::{Add, Range},
     str::FromStr,
 };
 
-use crate::parsing::FromBufRead;
+use crate::{
+    error::AocError,
+    parsing::{input_str, parse_lines_parallel, write_lines, ToBufWrite},
+    random::Rng,
+};
 
 #[derive(Clone, Eq, Hash, PartialEq)]
 struct Pos(i16, i16, i16);
@@ -35,7 +39,7 @@ impl Add<&Pos> for &Pos {
 }
 
 impl FromStr for Pos {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut iter = s.split(',').map(FromStr::from_str).tuples();
         let (x, y, z) = iter.next().ok_or("Did not get 3 elements")?;
@@ -46,6 +50,12 @@ impl FromStr for Pos {
     }
 }
 
+impl Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{},{}", self.0, self.1, self.2)
+    }
+}
+
 struct Droplet(HashSet<Pos>);
 
 impl Droplet {
@@ -67,6 +77,10 @@ impl Droplet {
             minmax(self.0.iter().map(|p| p.2).minmax()),
         ]
     }
+    fn parse(input: &[u8]) -> Result<Droplet, AocError> {
+        let cubes: Vec<Pos> = parse_lines_parallel(input_str(input)?)?;
+        Ok(Droplet(cubes.into_iter().collect()))
+    }
     fn count_surfaces(&self) -> usize {
         self.0
             .iter()
@@ -81,24 +95,26 @@ impl Droplet {
     }
 }
 
-impl FromBufRead for Droplet {
-    type Error = Box<dyn Error>;
-    fn read(br: &mut impl BufRead) -> Result<Self, Self::Error> {
-        Ok(Droplet(
-            br.lines()
-                .map_ok(|l| Pos::from_str(&l))
-                .collect::<Result<Result<HashSet<Pos>, _>, _>>()??,
-        ))
+impl ToBufWrite for Droplet {
+    // the cubes are a set, they are written in order
+    fn write(&self, bw: &mut impl Write) -> std::io::Result<()> {
+        write_lines(self.0.iter().sorted_by_key(|p| (p.0, p.1, p.2)), bw)
     }
 }
 
-pub fn a(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let droplet = Droplet::read(&mut buf)?;
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let mut out = Vec::new();
+    Droplet::parse(input)?.write(&mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<usize, AocError> {
+    let droplet = Droplet::parse(input)?;
     Ok(droplet.count_surfaces())
 }
 
-pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let droplet = Droplet::read(&mut buf)?;
+pub fn b(input: &[u8]) -> Result<usize, AocError> {
+    let droplet = Droplet::parse(input)?;
     let cube = droplet.get_bounding_cube();
     let mut water = Droplet(HashSet::new());
     water.0.extend([
@@ -136,3 +152,95 @@ pub fn b(mut buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
             + cube[0].len() * cube[2].len());
     Ok(water.count_surfaces() - outside_surface)
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // fill about half of a cube, which leaves some air pockets
+    let side = ((2 * size) as f64).cbrt().ceil() as i64;
+    let mut cubes = HashSet::new();
+    while cubes.len() < size.min((side * side * side) as usize) {
+        let cube = (
+            rng.range(0..=side - 1),
+            rng.range(0..=side - 1),
+            rng.range(0..=side - 1),
+        );
+        if cubes.insert(cube) {
+            writeln!(out, "{},{},{}", cube.0, cube.1, cube.2)?;
+        }
+    }
+    Ok(())
+}
+
+type ReferenceCube = (i32, i32, i32);
+
+const REFERENCE_SIDES: [ReferenceCube; 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+fn reference_cubes(buf: impl BufRead) -> Result<HashSet<ReferenceCube>, AocError> {
+    let mut cubes = HashSet::new();
+    for line in buf.lines() {
+        let line = line?;
+        let coordinates = line
+            .split(',')
+            .map(|c| c.parse())
+            .collect::<Result<Vec<i32>, _>>()?;
+        let [x, y, z] = coordinates[..] else {
+            Err("Expected 3 coordinates")?
+        };
+        cubes.insert((x, y, z));
+    }
+    Ok(cubes)
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let cubes = reference_cubes(buf)?;
+    let mut surface = 0;
+    for (x, y, z) in cubes.iter() {
+        for (dx, dy, dz) in REFERENCE_SIDES {
+            if !cubes.contains(&(x + dx, y + dy, z + dz)) {
+                surface += 1;
+            }
+        }
+    }
+    Ok(surface)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    // flood the space around the droplet and count the sides the water touches
+    let cubes = reference_cubes(buf)?;
+    let min = cubes
+        .iter()
+        .map(|c| c.0.min(c.1).min(c.2))
+        .min()
+        .unwrap_or(0)
+        - 1;
+    let max = cubes
+        .iter()
+        .map(|c| c.0.max(c.1).max(c.2))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut water = HashSet::from([(min, min, min)]);
+    let mut queue = vec![(min, min, min)];
+    let mut surface = 0;
+    while let Some((x, y, z)) = queue.pop() {
+        for (dx, dy, dz) in REFERENCE_SIDES {
+            let next = (x + dx, y + dy, z + dz);
+            if cubes.contains(&next) {
+                surface += 1;
+            } else if [next.0, next.1, next.2]
+                .iter()
+                .all(|c| (min..=max).contains(c))
+                && water.insert(next)
+            {
+                queue.push(next);
+            }
+        }
+    }
+    Ok(surface)
+}
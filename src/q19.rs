@@ -1,15 +1,19 @@
 use itertools::Itertools;
 use std::{
     collections::{hash_map::Entry, HashMap},
-    error::Error,
-    io::BufRead,
+    fmt::Display,
+    io::{BufRead, Write},
     mem::take,
     str::FromStr,
 };
 
 use crate::{
+    context::{Cancelled, Context},
+    error::AocError,
     optimize::{optimize, Optimize},
-    parsing::parse_by_line,
+    parsing::{input_str, parse_lines, write_lines},
+    random::Rng,
+    scan, trace,
 };
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -34,6 +38,18 @@ enum Material {
     Geode,
 }
 
+impl Display for Material {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Material::Ore => "ore",
+            Material::Clay => "clay",
+            Material::Obisidian => "obsidian",
+            Material::Geode => "geode",
+        };
+        write!(f, "{name}")
+    }
+}
+
 #[derive(Debug)]
 struct BotCost {
     output: Material,
@@ -46,53 +62,35 @@ struct BluePrint {
     max_num_bots: [usize; 4],
 }
 
-impl FromStr for Material {
-    type Err = &'static str;
+impl FromStr for BluePrint {
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let material = match s {
-            "ore" => Material::Ore,
-            "clay" => Material::Clay,
-            "obsidian" => Material::Obisidian,
-            "geode" => Material::Geode,
-            _ => Err("unknown material")?,
+        use Material::*;
+        let (id, ore_ore, clay_ore, obsidian_ore, obsidian_clay, geode_ore, geode_obsidian) = scan!(
+            s,
+            "Blueprint {}: Each ore robot costs {} ore. Each clay robot costs {} ore. \
+             Each obsidian robot costs {} ore and {} clay. \
+             Each geode robot costs {} ore and {} obsidian.",
+            usize,
+            usize,
+            usize,
+            usize,
+            usize,
+            usize,
+            usize
+        )?;
+        let bot = |output, cost: &[(Material, usize)]| BotCost {
+            output,
+            cost: cost.to_vec(),
         };
-        Ok(material)
-    }
-}
-
-impl FromStr for BotCost {
-    type Err = Box<dyn Error>;
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        s = s.strip_prefix(" Each ").ok_or("Expected Each")?;
-        let (material, mut s) = s.split_once(' ').ok_or("Expected material")?;
-        s = s.strip_prefix("robot costs ").ok_or("Expected robot")?;
-        let cost = s
-            .split(" and ")
-            .map(|num_mat| -> Result<(Material, usize), Box<dyn Error>> {
-                let (num, mat) = num_mat.split_once(' ').ok_or("Expected space")?;
-                Ok((mat.parse::<Material>()?, num.parse::<usize>()?))
-            })
-            .try_collect()?;
-        Ok(BotCost {
-            output: material.parse()?,
-            cost,
-        })
-    }
-}
-
-impl FromStr for BluePrint {
-    type Err = Box<dyn Error>;
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        s = s.strip_prefix("Blueprint ").ok_or("Parse error")?;
-        let (id, s) = s.split_once(':').ok_or("Expected :")?;
-        let mut rules = s
-            .split('.')
-            .filter(|p| !p.is_empty())
-            .map(|p| p.parse())
-            .collect::<Result<Vec<BotCost>, _>>()?;
-        rules.sort_by_key(|r| r.output);
+        let rules = [
+            bot(Ore, &[(Ore, ore_ore)]),
+            bot(Clay, &[(Ore, clay_ore)]),
+            bot(Obisidian, &[(Ore, obsidian_ore), (Clay, obsidian_clay)]),
+            bot(Geode, &[(Ore, geode_ore), (Obisidian, geode_obsidian)]),
+        ];
         let mut max_num_bots = [0; 4];
-        // println!("{rules:?}");
+        trace!(Debug, "Blueprint {id} rules: {rules:?}");
         for (material, max_num) in max_num_bots.iter_mut().enumerate() {
             *max_num = rules
                 .iter()
@@ -109,15 +107,31 @@ impl FromStr for BluePrint {
                 .unwrap_or(0);
         }
         max_num_bots[Material::Geode as usize] = 8_000_000_000;
-        // println!("{max_num_bots:?}");
+        trace!(Debug, "Blueprint {id} max bots: {max_num_bots:?}");
         Ok(BluePrint {
-            id: id.parse()?,
-            rules: rules.try_into().map_err(|_| "Expected 4 rules")?,
+            id,
+            rules,
             max_num_bots,
         })
     }
 }
 
+impl Display for BluePrint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Blueprint {}:", self.id)?;
+        for rule in self.rules.iter() {
+            let cost = rule.cost.iter().map(|(m, amount)| format!("{amount} {m}"));
+            write!(
+                f,
+                " Each {} robot costs {}.",
+                rule.output,
+                cost.format(" and ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl BotOutput {
     fn project(&self, time_left: usize) -> usize {
         self.output + self.num_bots * time_left
@@ -246,24 +260,153 @@ impl State {
 }
 
 impl BluePrint {
-    fn calc_max_geodes(&self, time: usize) -> usize {
+    fn calc_max_geodes(&self, time: usize, ctx: &Context) -> Result<usize, Cancelled> {
         let initial = State::new(time);
-        let solution = optimize(self, initial);
-        solution.bot_outputs[Material::Geode as usize].project(solution.time_left)
+        let solution = optimize(self, initial, ctx)?;
+        Ok(solution.bot_outputs[Material::Geode as usize].project(solution.time_left))
+    }
+}
+
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let blueprints: Vec<BluePrint> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&blueprints, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8], ctx: &Context) -> Result<usize, AocError> {
+    let mut sum = 0;
+    for blueprint in parse_lines::<BluePrint>(input_str(input)?) {
+        let blueprint = blueprint?;
+        sum += blueprint.id * blueprint.calc_max_geodes(24, ctx)?;
+    }
+    Ok(sum)
+}
+
+pub fn b(input: &[u8], ctx: &Context) -> Result<usize, AocError> {
+    let mut product = 1;
+    for blueprint in parse_lines::<BluePrint>(input_str(input)?).take(3) {
+        product *= blueprint?.calc_max_geodes(32, ctx)?;
     }
+    Ok(product)
 }
 
-pub fn a(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let sum: Result<Result<usize, _>, _> = parse_by_line::<BluePrint>(buf)
-        .map_ok(|blueprint| blueprint.map(|bp| bp.id * bp.calc_max_geodes(24)))
-        .sum();
-    sum?
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    for id in 1..=size {
+        writeln!(
+            out,
+            "Blueprint {id}: Each ore robot costs {} ore. Each clay robot costs {} ore. \
+             Each obsidian robot costs {} ore and {} clay. \
+             Each geode robot costs {} ore and {} obsidian.",
+            rng.range(2..=4),
+            rng.range(2..=4),
+            rng.range(2..=4),
+            rng.range(5..=20),
+            rng.range(2..=4),
+            rng.range(5..=20),
+        )?;
+    }
+    Ok(())
+}
+
+struct ReferenceBlueprint {
+    id: usize,
+    // the ore, clay and obsidian needed for an ore, clay, obsidian and geode robot
+    costs: [[usize; 3]; 4],
 }
 
-pub fn b(buf: impl BufRead) -> Result<usize, Box<dyn Error>> {
-    let product: Result<Result<usize, _>, _> = parse_by_line::<BluePrint>(buf)
-        .take(3)
-        .map_ok(|blueprint| blueprint.map(|bp| bp.calc_max_geodes(32)))
-        .product();
-    product?
+impl ReferenceBlueprint {
+    fn read(line: &str) -> Result<ReferenceBlueprint, AocError> {
+        let numbers = line
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse())
+            .collect::<Result<Vec<usize>, _>>()?;
+        let [id, ore_ore, clay_ore, obsidian_ore, obsidian_clay, geode_ore, geode_obsidian] =
+            numbers[..]
+        else {
+            Err("Expected 7 numbers")?
+        };
+        Ok(ReferenceBlueprint {
+            id,
+            costs: [
+                [ore_ore, 0, 0],
+                [clay_ore, 0, 0],
+                [obsidian_ore, obsidian_clay, 0],
+                [geode_ore, 0, geode_obsidian],
+            ],
+        })
+    }
+    // tries every order of building robots, waiting as long as needed for each one
+    fn search(
+        &self,
+        time_left: usize,
+        robots: [usize; 4],
+        materials: [usize; 4],
+        most_geodes: &mut usize,
+    ) {
+        let geodes = materials[3] + robots[3] * time_left;
+        *most_geodes = geodes.max(*most_geodes);
+        // even a new geode robot every minute cannot do better
+        if geodes + time_left * time_left.saturating_sub(1) / 2 <= *most_geodes {
+            return;
+        }
+        for robot in 0..4 {
+            // a factory builds one robot per minute, so more robots than any cost is pointless
+            if robot < 3 && self.costs.iter().all(|c| c[robot] <= robots[robot]) {
+                continue;
+            }
+            let mut wait = 0;
+            for material in 0..3 {
+                let needed = self.costs[robot][material].saturating_sub(materials[material]);
+                if needed > 0 {
+                    if robots[material] == 0 {
+                        wait = usize::MAX;
+                        break;
+                    }
+                    wait = wait.max(needed.div_ceil(robots[material]));
+                }
+            }
+            if wait >= time_left.saturating_sub(1) {
+                continue;
+            }
+            let mut next_materials = materials;
+            for material in 0..4 {
+                next_materials[material] += robots[material] * (wait + 1);
+            }
+            for (material, cost) in next_materials.iter_mut().zip(self.costs[robot]) {
+                *material -= cost;
+            }
+            let mut next_robots = robots;
+            next_robots[robot] += 1;
+            self.search(
+                time_left - wait - 1,
+                next_robots,
+                next_materials,
+                most_geodes,
+            );
+        }
+    }
+    fn most_geodes(&self, time: usize) -> usize {
+        let mut most_geodes = 0;
+        self.search(time, [1, 0, 0, 0], [0; 4], &mut most_geodes);
+        most_geodes
+    }
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<usize, AocError> {
+    let mut sum = 0;
+    for line in buf.lines() {
+        let blueprint = ReferenceBlueprint::read(&line?)?;
+        sum += blueprint.id * blueprint.most_geodes(24);
+    }
+    Ok(sum)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<usize, AocError> {
+    let mut product = 1;
+    for line in buf.lines().take(3) {
+        product *= ReferenceBlueprint::read(&line?)?.most_geodes(32);
+    }
+    Ok(product)
 }
@@ -1,7 +1,10 @@
-use itertools::Itertools;
-use std::{error::Error, io::BufRead};
+use std::io::{BufRead, Write};
 
-use crate::parsing::parse_by_line;
+use crate::{
+    error::AocError,
+    parsing::{input_str, parse_lines_parallel, write_lines},
+    random::Rng,
+};
 
 #[derive(Clone, Copy)]
 struct Number {
@@ -53,18 +56,84 @@ impl FromIterator<i64> for NumberList {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
-    let mut numbers = parse_by_line(buf).collect::<Result<Result<NumberList, _>, _>>()??;
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let numbers: Vec<i64> = parse_lines_parallel(input_str(input)?)?;
+    let mut out = Vec::new();
+    write_lines(&numbers, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i64, AocError> {
+    let mut numbers: NumberList = parse_lines_parallel(input_str(input)?)?
+        .into_iter()
+        .collect();
     numbers.mix();
     Ok(numbers.sum_positions_after_0(&[1000usize, 2000, 3000])?)
 }
 
-pub fn b(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
-    let mut numbers = parse_by_line::<i64>(buf)
-        .map_ok(|rn| rn.map(|n| n * 811_589_153))
-        .collect::<Result<Result<NumberList, _>, _>>()??;
+pub fn b(input: &[u8]) -> Result<i64, AocError> {
+    let mut numbers: NumberList = parse_lines_parallel::<i64>(input_str(input)?)?
+        .into_iter()
+        .map(|n| n * 811_589_153)
+        .collect();
     for _ in 0..10 {
         numbers.mix();
     }
     Ok(numbers.sum_positions_after_0(&[1000usize, 2000, 3000])?)
 }
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // exactly one 0, mixing a single number would divide by 0
+    let size = size.max(2);
+    let zero = rng.below(size);
+    for i in 0..size {
+        let n = if i == zero {
+            0
+        } else {
+            let n = rng.range(1..=10000);
+            if rng.ratio(1, 2) {
+                n
+            } else {
+                -n
+            }
+        };
+        writeln!(out, "{n}")?;
+    }
+    Ok(())
+}
+
+fn reference_decrypt(buf: impl BufRead, key: i64, rounds: usize) -> Result<i64, AocError> {
+    // (original position, value)
+    let mut numbers = Vec::new();
+    for (i, line) in buf.lines().enumerate() {
+        numbers.push((i, line?.parse::<i64>()? * key));
+    }
+    let len = numbers.len();
+    if len < 2 {
+        Err("Too few numbers")?;
+    }
+    for _ in 0..rounds {
+        for original in 0..len {
+            // every original position is there
+            let mut index = numbers.iter().position(|n| n.0 == original).unwrap();
+            // moving len - 1 steps around the circle gives the same order
+            for _ in 0..numbers[index].1.rem_euclid(len as i64 - 1) {
+                numbers.swap(index, (index + 1) % len);
+                index = (index + 1) % len;
+            }
+        }
+    }
+    let zero = numbers.iter().position(|n| n.1 == 0).ok_or("No 0 found")?;
+    Ok([1000, 2000, 3000]
+        .iter()
+        .map(|i| numbers[(zero + i) % len].1)
+        .sum())
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i64, AocError> {
+    reference_decrypt(buf, 1, 1)
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i64, AocError> {
+    reference_decrypt(buf, 811_589_153, 10)
+}
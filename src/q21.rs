@@ -1,7 +1,20 @@
 use itertools::Itertools;
-use std::{collections::HashMap, error::Error, io::BufRead, mem::replace, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::{BufRead, Write},
+    mem::replace,
+    str::FromStr,
+};
 
-use crate::parsing::parse_by_line;
+use crate::{
+    error::AocError,
+    parsing::{
+        combinators::{int, lit, parse_all, take_while1, PResult, Parser},
+        input_str, parse_lines, write_lines,
+    },
+    random::Rng,
+};
 
 #[derive(Clone)]
 enum Operation {
@@ -58,35 +71,55 @@ impl Operation {
     }
 }
 
-impl FromStr for Operation {
-    type Err = &'static str;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+impl Operation {
+    fn parse(input: &str) -> PResult<'_, Operation> {
         use Operation::*;
-        match s {
-            "+" => Ok(Add),
-            "-" => Ok(Sub),
-            "/" => Ok(Div),
-            "*" => Ok(Mul),
-            _ => Err("Unexpected operation"),
-        }
+        lit("+")
+            .map(|_| Add)
+            .or(lit("-").map(|_| Sub))
+            .or(lit("/").map(|_| Div))
+            .or(lit("*").map(|_| Mul))
+            .parse(input)
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Operation::*;
+        let symbol = match self {
+            Add => '+',
+            Sub => '-',
+            Div => '/',
+            Mul => '*',
+        };
+        write!(f, "{symbol}")
     }
 }
 
 impl FromStr for Monkey {
-    type Err = Box<dyn Error>;
+    type Err = AocError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (name, def) = s.split_once(": ").ok_or("Expected ': '")?;
-        if let Ok(n) = def.parse() {
-            Ok(Monkey {
-                name: name.to_string(),
-                shout: Shout::Number(n),
-            })
-        } else {
-            let (l, op, r) = def.split(' ').tuples().next().ok_or("Expected n <op> m")?;
-            Ok(Monkey {
-                name: name.to_string(),
-                shout: Shout::Operation((l.parse()?, op.parse()?, r.parse()?)),
-            })
+        let name =
+            || take_while1("monkey name", |c| c.is_ascii_alphabetic()).map(|n: &str| n.to_string());
+        let operation = (name(), lit(" "), Operation::parse, lit(" "), name())
+            .map(|(l, _, op, _, r)| Shout::Operation((l, op, r)));
+        let (name, _, shout) = parse_all(
+            (name(), lit(": "), operation.or(int().map(Shout::Number))),
+            s,
+        )?;
+        Ok(Monkey { name, shout })
+    }
+}
+
+impl Display for Monkey {
+    // Equal and None only come up while solving b, they are not in the input
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.name)?;
+        match &self.shout {
+            Shout::Equal((l, r)) => write!(f, "{l} = {r}"),
+            Shout::None => Ok(()),
+            Shout::Number(n) => write!(f, "{n}"),
+            Shout::Operation((l, op, r)) => write!(f, "{l} {op} {r}"),
         }
     }
 }
@@ -152,20 +185,233 @@ impl Monkeys {
     }
 }
 
-pub fn a(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
-    let monkeys_with_name: Result<Result<Vec<_>, _>, _> = parse_by_line::<Monkey>(buf).collect();
-    let monkeys = Monkeys::new(monkeys_with_name??.into_iter())?;
+pub fn reformat(input: &[u8]) -> Result<Vec<u8>, AocError> {
+    let monkeys: Vec<Monkey> = parse_lines(input_str(input)?).try_collect()?;
+    let mut out = Vec::new();
+    write_lines(&monkeys, &mut out)?;
+    Ok(out)
+}
+
+pub fn a(input: &[u8]) -> Result<i64, AocError> {
+    let monkeys_with_name: Result<Vec<_>, _> = parse_lines::<Monkey>(input_str(input)?).collect();
+    let monkeys = Monkeys::new(monkeys_with_name?.into_iter())?;
     Ok(monkeys.solve("root")?)
 }
 
-pub fn b(buf: impl BufRead) -> Result<i64, Box<dyn Error>> {
-    let monkeys_with_name: Result<Result<Vec<_>, _>, _> = parse_by_line::<Monkey>(buf).collect();
-    let mut monkeys = Monkeys::new(monkeys_with_name??.into_iter())?;
+pub fn b(input: &[u8]) -> Result<i64, AocError> {
+    let monkeys_with_name: Result<Vec<_>, _> = parse_lines::<Monkey>(input_str(input)?).collect();
+    let mut monkeys = Monkeys::new(monkeys_with_name?.into_iter())?;
     *monkeys.definitions.get_mut("humn").ok_or("No humn found")? = Shout::None;
     let root = monkeys.definitions.get_mut("root").ok_or("No root found")?;
     if let Shout::Operation((l, _, r)) = replace(root, Shout::None) {
         *root = Shout::Equal((l, r));
     }
     let _root_result = monkeys.solve_or_reverse("root", "root");
-    Ok(monkeys.solve("humn")?)
+    monkeys.solve("humn").map_err(AocError::no_solution)
+}
+
+struct MonkeyGenerator<'a> {
+    rng: &'a mut Rng,
+    next_name: usize,
+    lines: Vec<String>,
+}
+
+impl MonkeyGenerator<'_> {
+    // the monkey that ends up as the leaf on the path to humn has to be named humn
+    fn new_name(&mut self, humn: bool, num_monkeys: usize) -> String {
+        if humn && num_monkeys < 3 {
+            return "humn".into();
+        }
+        loop {
+            let mut n = self.next_name;
+            self.next_name += 1;
+            let name: String = (0..4)
+                .map(|_| {
+                    let c = (b'a' + (n % 26) as u8) as char;
+                    n /= 26;
+                    c
+                })
+                .collect();
+            if name != "humn" && name != "root" {
+                return name;
+            }
+        }
+    }
+    // Generates a tree of num_monkeys monkeys that shouts target. Every operand is positive
+    // and every division is exact, so b can reverse every operation on the path to humn.
+    fn generate(&mut self, name: String, target: i64, num_monkeys: usize, humn: bool) {
+        if num_monkeys < 3 {
+            self.lines.push(format!("{name}: {target}"));
+            return;
+        }
+        let left_size = 1 + self.rng.below(num_monkeys - 2);
+        let right_size = num_monkeys - 1 - left_size;
+        let humn_left = self.rng.ratio(1, 2);
+        let (op, left, right) = match self.rng.below(4) {
+            0 if target > 1 => {
+                let left = self.rng.range(1..=target - 1);
+                ('+', left, target - left)
+            }
+            2 if (2..=9).any(|d| target % d == 0) => {
+                let divisors = (2..=9).filter(|d| target % d == 0).collect::<Vec<_>>();
+                let right = *self.rng.choose(&divisors);
+                ('*', target / right, right)
+            }
+            3 if target < 1_000_000_000 => {
+                let right = self.rng.range(2..=5);
+                ('/', target * right, right)
+            }
+            _ => {
+                let right = self.rng.range(1..=20);
+                ('-', target + right, right)
+            }
+        };
+        let left_name = self.new_name(humn && humn_left, left_size);
+        let right_name = self.new_name(humn && !humn_left, right_size);
+        self.lines
+            .push(format!("{name}: {left_name} {op} {right_name}"));
+        self.generate(left_name, left, left_size, humn && humn_left);
+        self.generate(right_name, right, right_size, humn && !humn_left);
+    }
+}
+
+pub fn generate(rng: &mut Rng, size: usize, out: &mut impl Write) -> std::io::Result<()> {
+    // root compares two subtrees that shout the same number when humn shouts its own number
+    let num_monkeys = size.max(3) - 1;
+    let target = rng.range(1000..=1_000_000);
+    let mut generator = MonkeyGenerator {
+        rng,
+        next_name: 0,
+        lines: Vec::new(),
+    };
+    let left_size = num_monkeys / 2;
+    let left = generator.new_name(true, left_size);
+    let right = generator.new_name(false, num_monkeys - left_size);
+    generator.lines.push(format!("root: {left} + {right}"));
+    generator.generate(left, target, left_size, true);
+    generator.generate(right, target, num_monkeys - left_size, false);
+    let mut lines = generator.lines;
+    rng.shuffle(&mut lines);
+    for line in lines {
+        writeln!(out, "{line}")?;
+    }
+    Ok(())
+}
+
+enum ReferenceJob {
+    Number(i64),
+    Operation(String, String, String),
+}
+
+fn reference_jobs(buf: impl BufRead) -> Result<HashMap<String, ReferenceJob>, AocError> {
+    let mut jobs = HashMap::new();
+    for line in buf.lines() {
+        let line = line?;
+        let (name, job) = line.split_once(": ").ok_or("Expected ': '")?;
+        let words: Vec<&str> = job.split(' ').collect();
+        let job = match words[..] {
+            [number] => ReferenceJob::Number(number.parse()?),
+            [l, op, r] => ReferenceJob::Operation(l.into(), op.into(), r.into()),
+            _ => Err("Expected n <op> m")?,
+        };
+        jobs.insert(name.to_string(), job);
+    }
+    Ok(jobs)
+}
+
+fn reference_number(jobs: &HashMap<String, ReferenceJob>, name: &str) -> Result<i64, AocError> {
+    match jobs.get(name).ok_or("Could not find monkey")? {
+        ReferenceJob::Number(n) => Ok(*n),
+        ReferenceJob::Operation(l, op, r) => {
+            let (l, r) = (reference_number(jobs, l)?, reference_number(jobs, r)?);
+            match op.as_str() {
+                "+" => Ok(l + r),
+                "-" => Ok(l - r),
+                "*" => Ok(l * r),
+                "/" => Ok(l.checked_div(r).ok_or("Division by 0")?),
+                _ => Err("Unexpected operation".into()),
+            }
+        }
+    }
+}
+
+// h * humn + c
+#[derive(Clone, Copy)]
+struct ReferenceLinear(i128, i128);
+
+impl ReferenceLinear {
+    fn add(self, rhs: ReferenceLinear) -> ReferenceLinear {
+        ReferenceLinear(self.0 + rhs.0, self.1 + rhs.1)
+    }
+    fn mul(self, rhs: ReferenceLinear) -> Result<ReferenceLinear, AocError> {
+        if self.0 != 0 && rhs.0 != 0 {
+            Err("humn is used more than once")?;
+        }
+        Ok(ReferenceLinear(
+            self.0 * rhs.1 + self.1 * rhs.0,
+            self.1 * rhs.1,
+        ))
+    }
+}
+
+// numerator / denominator, exact rational arithmetic instead of integer division
+type ReferenceFraction = (ReferenceLinear, ReferenceLinear);
+
+fn reference_reduced((n, d): ReferenceFraction) -> ReferenceFraction {
+    let gcd = |mut a: i128, mut b: i128| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a.abs()
+    };
+    match gcd(gcd(n.0, n.1), gcd(d.0, d.1)) {
+        0 => (n, d),
+        g => (
+            ReferenceLinear(n.0 / g, n.1 / g),
+            ReferenceLinear(d.0 / g, d.1 / g),
+        ),
+    }
+}
+
+fn reference_fraction(
+    jobs: &HashMap<String, ReferenceJob>,
+    name: &str,
+) -> Result<ReferenceFraction, AocError> {
+    if name == "humn" {
+        return Ok((ReferenceLinear(1, 0), ReferenceLinear(0, 1)));
+    }
+    match jobs.get(name).ok_or("Could not find monkey")? {
+        ReferenceJob::Number(n) => Ok((ReferenceLinear(0, *n as i128), ReferenceLinear(0, 1))),
+        ReferenceJob::Operation(l, op, r) => {
+            let (ln, ld) = reference_fraction(jobs, l)?;
+            let (rn, rd) = reference_fraction(jobs, r)?;
+            let negate = ReferenceLinear(0, -1);
+            Ok(reference_reduced(match op.as_str() {
+                "+" => (ln.mul(rd)?.add(rn.mul(ld)?), ld.mul(rd)?),
+                "-" => (ln.mul(rd)?.add(rn.mul(ld)?.mul(negate)?), ld.mul(rd)?),
+                "*" => (ln.mul(rn)?, ld.mul(rd)?),
+                "/" => (ln.mul(rd)?, ld.mul(rn)?),
+                _ => Err("Unexpected operation")?,
+            }))
+        }
+    }
+}
+
+pub fn reference_a(buf: impl BufRead) -> Result<i64, AocError> {
+    reference_number(&reference_jobs(buf)?, "root")
+}
+
+pub fn reference_b(buf: impl BufRead) -> Result<i64, AocError> {
+    let jobs = reference_jobs(buf)?;
+    let ReferenceJob::Operation(l, _, r) = jobs.get("root").ok_or("No root found")? else {
+        Err("root does not compare")?
+    };
+    // ln / ld = rn / rd, so ln * rd - rn * ld = 0
+    let (ln, ld) = reference_fraction(&jobs, l)?;
+    let (rn, rd) = reference_fraction(&jobs, r)?;
+    let ReferenceLinear(h, c) = ln.mul(rd)?.add(rn.mul(ld)?.mul(ReferenceLinear(0, -1))?);
+    if h == 0 || c % h != 0 {
+        Err("No integer solution")?;
+    }
+    Ok((-c / h).try_into()?)
 }
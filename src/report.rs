@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Write as _,
+    fs::{read_to_string, write, OpenOptions},
+    io::{ErrorKind, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::runner::{RunResult, Status};
+
+// data/timings keeps `<unix time> <part> <input> <microseconds>` for every successful part of
+// every report, the sparklines are drawn from it
+type History = HashMap<String, Vec<u128>>;
+
+const SPARKLINE_POINTS: usize = 30;
+
+fn key(result: &RunResult) -> String {
+    format!("{} {}", result.name, result.input.display())
+}
+
+fn succeeded(result: &RunResult) -> bool {
+    matches!(result.status, Status::Correct | Status::Unchecked)
+}
+
+pub fn append_history(data_dir: &Path, results: &[RunResult]) -> Result<(), Box<dyn Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_dir.join("timings"))?;
+    for result in results.iter().filter(|r| succeeded(r)) {
+        writeln!(file, "{now} {} {}", key(result), result.elapsed.as_micros())?;
+    }
+    Ok(())
+}
+
+pub fn load_history(data_dir: &Path) -> Result<History, Box<dyn Error>> {
+    let content = match read_to_string(data_dir.join("timings")) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(History::new()),
+        Err(e) => Err(e)?,
+    };
+    let mut history = History::new();
+    for line in content.lines().filter(|line| !line.is_empty()) {
+        let mut parts = line.split(' ');
+        let (Some(_), Some(name), Some(input), Some(micros), None) = (
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+            parts.next(),
+        ) else {
+            Err(format!("Could not read timings line {line}"))?
+        };
+        history
+            .entry(format!("{name} {input}"))
+            .or_default()
+            .push(micros.parse()?);
+    }
+    Ok(history)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn format_duration(duration: Duration) -> String {
+    match duration.as_micros() {
+        us if us < 10_000 => format!("{us}µs"),
+        us if us < 10_000_000 => format!("{}ms", us / 1000),
+        us => format!("{:.1}s", us as f64 / 1e6),
+    }
+}
+
+// days since 1970-01-01 to a calendar date, from Howard Hinnant's civil_from_days
+fn date(unix_seconds: u64) -> String {
+    let days = (unix_seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let seconds = unix_seconds % 86400;
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02} UTC",
+        seconds / 3600,
+        seconds / 60 % 60
+    )
+}
+
+fn sparkline(points: &[u128]) -> String {
+    let points = &points[points.len().saturating_sub(SPARKLINE_POINTS)..];
+    if points.len() < 2 {
+        return String::new();
+    }
+    let (width, height) = (120.0, 20.0);
+    let max = *points.iter().max().unwrap() as f64; // cannot fail, there are two points
+    let min = *points.iter().min().unwrap() as f64;
+    let range = (max - min).max(1.0);
+    let coordinates: Vec<String> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &p)| {
+            let x = i as f64 * width / (points.len() - 1) as f64;
+            let y = 1.0 + (height - 2.0) * (max - p as f64) / range;
+            format!("{x:.1},{y:.1}")
+        })
+        .collect();
+    format!(
+        r##"<svg width="{width}" height="{height}"><polyline points="{}" fill="none" stroke="#36c" stroke-width="1.5"/></svg>"##,
+        coordinates.join(" ")
+    )
+}
+
+// one bar per part on a log scale, solve times span microseconds to seconds
+fn bar_chart(results: &[RunResult]) -> String {
+    let timed: Vec<&RunResult> = results.iter().filter(|r| succeeded(r)).collect();
+    let (label_width, bar_width, row_height) = (70.0, 500.0, 18.0);
+    let max_log = timed
+        .iter()
+        .map(|r| (r.elapsed.as_micros().max(1) as f64).log10())
+        .fold(1.0, f64::max);
+    let mut svg = format!(
+        r#"<svg width="{}" height="{}">"#,
+        label_width + bar_width + 80.0,
+        row_height * timed.len() as f64
+    );
+    for (i, result) in timed.iter().enumerate() {
+        let y = i as f64 * row_height;
+        let log = (result.elapsed.as_micros().max(1) as f64).log10();
+        let width = (bar_width * log / max_log).max(1.0);
+        let _ = write!(
+            svg,
+            r##"<text x="0" y="{}">{}</text><rect x="{label_width}" y="{}" width="{width:.1}" height="{}" fill="#36c"/><text x="{:.1}" y="{}">{}</text>"##,
+            y + 13.0,
+            escape(&result.name),
+            y + 2.0,
+            row_height - 4.0,
+            label_width + width + 5.0,
+            y + 13.0,
+            format_duration(result.elapsed)
+        );
+    }
+    svg + "</svg>"
+}
+
+// The report never contains answers or error messages, which can quote the input, so it can
+// be shared
+pub fn write_report(
+    path: &Path,
+    results: &[RunResult],
+    history: &History,
+) -> Result<(), Box<dyn Error>> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let total: Duration = results.iter().map(|r| r.elapsed).sum();
+    let mut rows = String::new();
+    for result in results {
+        let (class, status) = match &result.status {
+            Status::Correct => ("ok", "correct".to_string()),
+            Status::Unchecked => ("unchecked", "no stored answer".to_string()),
+            Status::Wrong(_) => ("fail", "wrong answer".to_string()),
+            Status::Error(e) => ("fail", format!("error: {}", e.kind().replace('_', " "))),
+        };
+        let time = if succeeded(result) {
+            format_duration(result.elapsed)
+        } else {
+            String::new()
+        };
+        let empty = Vec::new();
+        let _ = writeln!(
+            rows,
+            r#"<tr><td>{}</td><td>{}</td><td class="{class}">{}</td><td class="time">{time}</td><td>{}</td></tr>"#,
+            escape(&result.name),
+            escape(&result.input.display().to_string()),
+            escape(&status),
+            sparkline(history.get(&key(result)).unwrap_or(&empty)),
+        );
+    }
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Advent of Code 2022 report</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+td, th {{ padding: 2px 10px; border-bottom: 1px solid #ddd; text-align: left; }}
+.ok {{ color: #080; }}
+.unchecked {{ color: #888; }}
+.fail {{ color: #c00; }}
+.time {{ text-align: right; }}
+svg text {{ font-size: 12px; }}
+</style>
+</head>
+<body>
+<h1>Advent of Code 2022 report</h1>
+<p>{} parts run on {}, {} in total.</p>
+<table>
+<tr><th>Part</th><th>Input</th><th>Result</th><th>Time</th><th>History</th></tr>
+{rows}</table>
+<h2>Solve times (log scale)</h2>
+{}
+</body>
+</html>
+"#,
+        results.len(),
+        date(now),
+        format_duration(total),
+        bar_chart(results),
+    );
+    write(path, html)?;
+    Ok(())
+}
@@ -1,55 +1,155 @@
-use itertools::Itertools;
 use std::{
-    collections::HashMap,
-    env::args,
+    cell::RefCell,
+    collections::HashSet,
     error::Error,
     fmt::Display,
-    fs::File,
-    io::BufRead,
-    io::BufReader,
+    io::{stderr, IsTerminal},
     path::{Path, PathBuf},
-    time::Instant,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
 };
 
+use crate::{
+    answers::Answers,
+    context::Context,
+    error::AocError,
+    parsing::normalize,
+    secret::{read_input, Key},
+    usage::Usage,
+};
+
+#[derive(PartialEq)]
+enum Format {
+    Text,
+    // adds the resource usage of every part
+    Verbose,
+    // one JSON object per part
+    Json,
+}
+
+pub enum Status {
+    Correct,
+    // the expected answer is only known when it is not hashed
+    Wrong(Option<String>),
+    // no answer stored yet
+    Unchecked,
+    Error(AocError),
+}
+
+pub struct RunResult {
+    pub name: String,
+    pub input: PathBuf,
+    pub status: Status,
+    pub answer: Option<String>,
+    pub elapsed: Duration,
+    pub usage: Usage,
+}
+
 pub struct Runner {
     data_dir: PathBuf,
-    answers: HashMap<String, String>,
+    answers: Answers,
     test_to_run: Option<String>,
+    format: Format,
+    key: Option<Key>,
+    timeout: Option<Duration>,
+    results: RefCell<Vec<RunResult>>,
+    // inputs that were already reported as normalized, both parts read the same file
+    normalized: RefCell<HashSet<PathBuf>>,
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// Runs next to a solver until it is done: cancels the solver on a timeout and, if stderr
+// is a terminal, keeps a progress line up to date once the solver takes a while
+fn monitor(
+    name: &str,
+    ctx: &Context,
+    timeout: Option<Duration>,
+    show_progress: bool,
+    done: &AtomicBool,
+) {
+    let start = Instant::now();
+    let mut shown = false;
+    // the solver unparks this thread when it is done, so it does not wait for the timeout
+    while !done.load(Ordering::Relaxed) {
+        thread::park_timeout(Duration::from_millis(100));
+        let elapsed = start.elapsed();
+        if timeout.is_some_and(|timeout| elapsed > timeout) {
+            ctx.cancel();
+        }
+        if show_progress && elapsed > Duration::from_millis(500) {
+            let mut line = format!("{name} running for {:.1}s", elapsed.as_secs_f64());
+            if let Some(progress) = ctx.progress() {
+                line += &format!(", {:.1}% done", progress * 100.0);
+            }
+            if ctx.states() > 0 {
+                line += &format!(", {} states", ctx.states());
+            }
+            eprint!("\r{line}\x1b[K");
+            shown = true;
+        }
+    }
+    if shown {
+        eprint!("\r\x1b[K");
+    }
 }
 
 impl Runner {
-    pub fn with_data_dir(data_dir: impl AsRef<Path>) -> Result<Runner, Box<dyn Error>> {
+    pub fn with_data_dir(
+        data_dir: impl AsRef<Path>,
+        args: &[String],
+    ) -> Result<Runner, Box<dyn Error>> {
         let data_dir = data_dir.as_ref().to_path_buf();
-        let path = data_dir.join("answers");
-        let buf_read = BufReader::new(File::open(path)?);
-        let answers = buf_read
-            .lines()
-            .filter_ok(|line| !line.is_empty())
-            .map_ok(|line| {
-                let (func, input, answer) = line
-                    .splitn(3, ' ')
-                    .tuples()
-                    .next()
-                    .expect("Could not read answers");
-                (format!("{func} {input}"), answer.to_string())
-            })
-            .try_collect()?;
-        let args: Vec<String> = args().skip(1).collect();
-        if args.len() > 2 {
+        let answers = Answers::load(&data_dir)?;
+        let key = Key::load(&data_dir)?;
+        let mut format = Format::Text;
+        let mut timeout = None;
+        let mut positional: Vec<&String> = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--verbose" => format = Format::Verbose,
+                "--json" => format = Format::Json,
+                flag if flag.starts_with("--timeout=") => {
+                    let seconds: f64 = flag["--timeout=".len()..].parse()?;
+                    timeout = Some(Duration::from_secs_f64(seconds));
+                }
+                flag if flag.starts_with("--") => Err(format!("Unknown flag {flag}"))?,
+                _ => positional.push(arg),
+            }
+        }
+        if positional.len() > 2 {
             Err("Too many arguments")?;
         }
         Ok(Runner {
             data_dir,
             answers,
-            test_to_run: args.first().cloned(),
+            test_to_run: positional.first().map(|s| s.to_string()),
+            format,
+            key,
+            timeout,
+            results: RefCell::new(Vec::new()),
+            normalized: RefCell::new(HashSet::new()),
         })
     }
 
     pub fn run_test<R, E, F>(&self, name: &str, func: F, filename: impl AsRef<Path>)
     where
         R: Display,
-        E: Display,
-        F: FnOnce(BufReader<File>) -> Result<R, E>,
+        E: Into<AocError>,
+        F: FnOnce(&[u8], &Context) -> Result<R, E>,
     {
         if let Some(test_to_run) = self.test_to_run.as_ref() {
             if !name.starts_with(test_to_run) {
@@ -57,27 +157,135 @@ impl Runner {
             }
         }
         let full_path = self.data_dir.join(&filename);
-        let Ok(input_file) = File::open(&full_path) else {
-            eprintln!("Could not open file {}", full_path.display());
-            return;
+        let mut result = RunResult {
+            name: name.to_string(),
+            input: full_path.clone(),
+            status: Status::Unchecked,
+            answer: None,
+            elapsed: Duration::ZERO,
+            usage: Usage::default(),
         };
-        let input = BufReader::new(input_file);
-        let start = Instant::now();
-        let result = func(input);
-        let elapsed = start.elapsed();
+        let input = match read_input(&full_path, self.key.as_ref()) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("{e}");
+                result.status = Status::Error(e.into());
+                return self.results.borrow_mut().push(result);
+            }
+        };
+        let (input, changes) = normalize(&input);
+        if !changes.is_empty() && self.normalized.borrow_mut().insert(full_path.clone()) {
+            eprintln!(
+                "{} was normalized: {}",
+                full_path.display(),
+                changes.join(", ")
+            );
+        }
         let name = format!("{name} {}", full_path.display());
-        match result {
-            Err(e) => eprintln!("{name} had an error: {e}"),
-            Ok(r) => {
-                let result = format!("{r}");
-                match self.answers.get(&name) {
-                    None => println!("{name} {result}"),
-                    Some(a) if *a == result => {
-                        println!("{name} solved in {}us", elapsed.as_micros())
-                    }
-                    Some(a) => eprintln!("{name} computed {result}, expected {a}"),
-                }
+        let ctx = Context::new();
+        let done = AtomicBool::new(false);
+        let show_progress = stderr().is_terminal() && self.format != Format::Json;
+        let timeout = self.timeout;
+        let answer = thread::scope(|scope| {
+            let monitor = scope.spawn(|| monitor(&name, &ctx, timeout, show_progress, &done));
+            let start_usage = Usage::now();
+            let start = Instant::now();
+            let answer = func(&input, &ctx);
+            result.elapsed = start.elapsed();
+            result.usage = Usage::now().since(&start_usage);
+            done.store(true, Ordering::Relaxed);
+            monitor.thread().unpark();
+            answer
+        });
+        match answer {
+            Err(_) if ctx.is_cancelled() => {
+                result.status = Status::Error(AocError::TimedOut(result.elapsed))
+            }
+            Err(e) => result.status = Status::Error(e.into()),
+            Ok(answer) => {
+                let answer = answer.to_string();
+                result.status = match self.answers.get(&name) {
+                    None => Status::Unchecked,
+                    Some(a) if a.matches(&answer) => Status::Correct,
+                    Some(a) => Status::Wrong(a.plain().map(str::to_string)),
+                };
+                result.answer = Some(answer);
             }
         }
+        self.print(&name, &result);
+        self.results.borrow_mut().push(result);
+    }
+
+    fn print(&self, name: &str, result: &RunResult) {
+        let usage = &result.usage;
+        let answer = result.answer.as_deref().unwrap_or_default();
+        if self.format == Format::Json {
+            let (status, details) = match &result.status {
+                Status::Error(e) => (
+                    "error",
+                    format!(
+                        r#""error":{},"error_kind":"{}""#,
+                        json_string(&e.to_string()),
+                        e.kind()
+                    ),
+                ),
+                Status::Unchecked => ("unchecked", format!(r#""answer":{}"#, json_string(answer))),
+                Status::Correct => ("correct", format!(r#""answer":{}"#, json_string(answer))),
+                Status::Wrong(Some(a)) => (
+                    "wrong",
+                    format!(
+                        r#""answer":{},"expected":{}"#,
+                        json_string(answer),
+                        json_string(a)
+                    ),
+                ),
+                Status::Wrong(None) => ("wrong", format!(r#""answer":{}"#, json_string(answer))),
+            };
+            println!(
+                r#"{{"name":{},"input":{},"status":"{status}",{details},"elapsed_us":{},"user_us":{},"sys_us":{},"max_rss_delta_kb":{},"minor_faults":{},"major_faults":{},"voluntary_switches":{},"involuntary_switches":{}}}"#,
+                json_string(&result.name),
+                json_string(&result.input.display().to_string()),
+                result.elapsed.as_micros(),
+                usage.user.as_micros(),
+                usage.sys.as_micros(),
+                usage.max_rss_kb,
+                usage.minor_faults,
+                usage.major_faults,
+                usage.voluntary_switches,
+                usage.involuntary_switches,
+            );
+            return;
+        }
+        match &result.status {
+            Status::Error(e) => eprintln!("{name} had an error: {e}"),
+            Status::Unchecked => println!("{name} {answer}"),
+            Status::Correct => println!("{name} solved in {}us", result.elapsed.as_micros()),
+            Status::Wrong(Some(a)) => eprintln!("{name} computed {answer}, expected {a}"),
+            Status::Wrong(None) => {
+                eprintln!("{name} computed {answer}, which does not match the stored hash")
+            }
+        }
+        if self.format == Format::Verbose {
+            println!(
+                "{name} used {}us user {}us sys, max rss +{}kB, {} minor {} major faults, {} voluntary {} involuntary switches",
+                usage.user.as_micros(),
+                usage.sys.as_micros(),
+                usage.max_rss_kb,
+                usage.minor_faults,
+                usage.major_faults,
+                usage.voluntary_switches,
+                usage.involuntary_switches,
+            );
+        }
+    }
+
+    pub fn finish(&self, elapsed: Duration) {
+        if self.format != Format::Json {
+            println!("Ran all puzzles in {}ms", elapsed.as_millis());
+        }
+    }
+
+    pub fn into_results(self) -> Vec<RunResult> {
+        self.results.into_inner()
     }
 }
@@ -0,0 +1,204 @@
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2::{self, PBKDF2_HMAC_SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    env,
+    error::Error,
+    fs::{read, read_to_string, write, OpenOptions},
+    io::{ErrorKind, Write},
+    num::NonZeroU32,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+// The local secret lives in data/key or AOC_KEY as 64 hex digits. It must never be committed.
+pub struct Key(LessSafeKey);
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if !hex.len().is_multiple_of(2) {
+        Err("Odd number of hex digits")?;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            Ok(u8::from_str_radix(
+                hex.get(i..i + 2).ok_or("Expected hex")?,
+                16,
+            )?)
+        })
+        .collect()
+}
+
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    // the system random source only fails if the OS has none at all
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("No system random source");
+    bytes
+}
+
+pub fn pbkdf2_hash(iterations: NonZeroU32, salt: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut hash = vec![0; 32];
+    pbkdf2::derive(PBKDF2_HMAC_SHA256, iterations, salt, secret, &mut hash);
+    hash
+}
+
+pub fn pbkdf2_matches(iterations: NonZeroU32, salt: &[u8], secret: &[u8], hash: &[u8]) -> bool {
+    pbkdf2::verify(PBKDF2_HMAC_SHA256, iterations, salt, secret, hash).is_ok()
+}
+
+impl Key {
+    fn from_hex(hex: &str) -> Result<Key, Box<dyn Error>> {
+        let bytes = from_hex(hex.trim())?;
+        let key =
+            UnboundKey::new(&CHACHA20_POLY1305, &bytes).map_err(|_| "Key must be 32 bytes")?;
+        Ok(Key(LessSafeKey::new(key)))
+    }
+
+    // AOC_KEY takes precedence over the key file
+    pub fn load(data_dir: &Path) -> Result<Option<Key>, Box<dyn Error>> {
+        if let Ok(hex) = env::var("AOC_KEY") {
+            return Ok(Some(
+                Key::from_hex(&hex).map_err(|e| format!("AOC_KEY: {e}"))?,
+            ));
+        }
+        let path = data_dir.join("key");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let key = Key::from_hex(&read_to_string(&path)?);
+        Ok(Some(key.map_err(|e| format!("{}: {e}", path.display()))?))
+    }
+
+    pub fn load_required(data_dir: &Path) -> Result<Key, Box<dyn Error>> {
+        Key::load(data_dir)?.ok_or_else(|| {
+            format!(
+                "No key found, set AOC_KEY or create {} with the keygen command",
+                data_dir.join("key").display()
+            )
+            .into()
+        })
+    }
+
+    pub fn generate(data_dir: &Path) -> Result<(), Box<dyn Error>> {
+        let path = data_dir.join("key");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .map_err(|e| format!("Could not create {}: {e}", path.display()))?;
+        writeln!(file, "{}", to_hex(&random_bytes::<32>()))?;
+        Ok(())
+    }
+
+    // the context is authenticated but not encrypted, so a sealed value cannot be moved to
+    // another place that uses a different context
+    pub fn seal(&self, context: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let nonce = random_bytes::<NONCE_LEN>();
+        let mut sealed = plaintext.to_vec();
+        self.0
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(context),
+                &mut sealed,
+            )
+            .expect("Plaintext too large to seal");
+        [&nonce[..], &sealed].concat()
+    }
+
+    pub fn open(&self, context: &[u8], sealed: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        if sealed.len() < NONCE_LEN {
+            Err("Sealed data too short")?;
+        }
+        let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce")?;
+        let mut opened = sealed.to_vec();
+        let len = self
+            .0
+            .open_in_place(nonce, Aad::from(context), &mut opened)
+            .map_err(|_| "Could not decrypt, wrong key or corrupted data")?
+            .len();
+        opened.truncate(len);
+        Ok(opened)
+    }
+}
+
+// An encrypted input starts with this line, followed by the input sealed with its file name
+const ENCRYPTED_MAGIC: &[u8] = b"aoc-encrypted-v1\n";
+
+fn file_name(path: &Path) -> Result<&[u8], Box<dyn Error>> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    Ok(name.as_encoded_bytes())
+}
+
+fn encrypted_path(path: &Path) -> PathBuf {
+    let mut encrypted = path.as_os_str().to_owned();
+    encrypted.push(".enc");
+    PathBuf::from(encrypted)
+}
+
+// Reads an input that may be encrypted, either in place or next to it as <path>.enc
+pub fn read_input(path: &Path, key: Option<&Key>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (data, data_path) = match read(path) {
+        Ok(data) => (data, path.to_path_buf()),
+        Err(e) if e.kind() == ErrorKind::NotFound && encrypted_path(path).exists() => {
+            (read(encrypted_path(path))?, encrypted_path(path))
+        }
+        Err(e) => Err(format!("Could not open file {}: {e}", path.display()))?,
+    };
+    let Some(sealed) = data.strip_prefix(ENCRYPTED_MAGIC) else {
+        return Ok(data);
+    };
+    let key = key.ok_or_else(|| {
+        format!(
+            "{} is encrypted, set AOC_KEY or put the key in data/key",
+            data_path.display()
+        )
+    })?;
+    key.open(file_name(path)?, sealed)
+        .map_err(|e| format!("{}: {e}", data_path.display()).into())
+}
+
+// encrypt <file>... writes every file encrypted to <file>.enc
+pub fn encrypt_files(data_dir: &Path, paths: &[String]) -> Result<(), Box<dyn Error>> {
+    let key = Key::load_required(data_dir)?;
+    for path in paths.iter().map(Path::new) {
+        let data = read(path)?;
+        if data.starts_with(ENCRYPTED_MAGIC) {
+            Err(format!("{} is already encrypted", path.display()))?;
+        }
+        let sealed = key.seal(file_name(path)?, &data);
+        write(encrypted_path(path), [ENCRYPTED_MAGIC, &sealed].concat())?;
+        println!(
+            "Encrypted {} to {}",
+            path.display(),
+            encrypted_path(path).display()
+        );
+    }
+    Ok(())
+}
+
+// decrypt <file>.enc... writes every file decrypted to <file>
+pub fn decrypt_files(data_dir: &Path, paths: &[String]) -> Result<(), Box<dyn Error>> {
+    let key = Key::load_required(data_dir)?;
+    for encrypted in paths.iter().map(Path::new) {
+        let path = encrypted
+            .to_str()
+            .and_then(|p| p.strip_suffix(".enc"))
+            .ok_or_else(|| format!("{} does not end in .enc", encrypted.display()))?;
+        let data = read_input(Path::new(path), Some(&key))?;
+        write(path, data)?;
+        println!("Decrypted {} to {path}", encrypted.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Arguments,
+    fs::{File, OpenOptions},
+    io::{stderr, Write},
+    sync::Mutex,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn parse(s: &str) -> Option<Level> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Some(Level::Off),
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+// AOC_TRACE is a comma separated list like "info,q17=debug,q12=off", an entry without a
+// module sets the level for every module
+struct Filter {
+    default: Level,
+    modules: HashMap<String, Level>,
+    // the highest level of any entry, so a disabled trace costs a single comparison
+    max: Level,
+}
+
+impl Filter {
+    fn from_env() -> Filter {
+        let mut filter = Filter {
+            default: Level::Off,
+            modules: HashMap::new(),
+            max: Level::Off,
+        };
+        for entry in env::var("AOC_TRACE").unwrap_or_default().split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (module, level) = match entry.split_once('=') {
+                Some((module, level)) => (Some(module), level),
+                None => (None, entry),
+            };
+            match (module, Level::parse(level)) {
+                (_, None) => eprintln!("Ignoring AOC_TRACE entry {entry}"),
+                (None, Some(level)) => filter.default = level,
+                (Some(module), Some(level)) => {
+                    filter.modules.insert(module.to_string(), level);
+                }
+            }
+        }
+        filter.max = filter
+            .modules
+            .values()
+            .copied()
+            .fold(filter.default, Level::max);
+        filter
+    }
+}
+
+lazy_static! {
+    static ref FILTER: Filter = Filter::from_env();
+    // AOC_TRACE_FILE sends traces to a file instead of stderr, if it can be opened
+    static ref FILE: Option<Mutex<File>> = env::var_os("AOC_TRACE_FILE").and_then(|path| {
+        match OpenOptions::new().create(true).append(true).open(&path) {
+            Ok(file) => Some(Mutex::new(file)),
+            Err(e) => {
+                eprintln!("Could not open AOC_TRACE_FILE, tracing to stderr: {e}");
+                None
+            }
+        }
+    });
+}
+
+// module_path!() gives advent_of_rust_2022::q17, filters use the last part
+fn short_module(module_path: &str) -> &str {
+    module_path.rsplit("::").next().unwrap_or(module_path)
+}
+
+pub fn enabled(level: Level, module_path: &str) -> bool {
+    if level > FILTER.max {
+        return false;
+    }
+    let module = short_module(module_path);
+    level <= *FILTER.modules.get(module).unwrap_or(&FILTER.default)
+}
+
+pub fn write(level: Level, module_path: &str, args: Arguments) {
+    let module = short_module(module_path);
+    // a failing trace must never fail the solver, so write errors are ignored
+    let _ = match FILE.as_ref() {
+        Some(file) => writeln!(file.lock().unwrap(), "[{level:?} {module}] {args}"),
+        None => writeln!(stderr().lock(), "[{level:?} {module}] {args}"),
+    };
+}
+
+// trace!(Debug, "format {}", args) only evaluates its arguments when the level is enabled
+#[macro_export]
+macro_rules! trace {
+    ($level:ident, $($arg:tt)*) => {
+        if $crate::trace::enabled($crate::trace::Level::$level, module_path!()) {
+            $crate::trace::write($crate::trace::Level::$level, module_path!(), format_args!($($arg)*));
+        }
+    };
+}
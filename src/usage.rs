@@ -0,0 +1,49 @@
+use std::time::Duration;
+
+// what getrusage reports for the whole process, or the difference between two snapshots
+#[derive(Clone, Copy, Default)]
+pub struct Usage {
+    pub user: Duration,
+    pub sys: Duration,
+    // the peak can only grow, so a difference is how much a part raised it
+    pub max_rss_kb: i64,
+    pub minor_faults: i64,
+    pub major_faults: i64,
+    pub voluntary_switches: i64,
+    pub involuntary_switches: i64,
+}
+
+fn duration(time: libc::timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+impl Usage {
+    pub fn now() -> Usage {
+        let mut usage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+        // getrusage only fails for an invalid `who`, then the counters stay zero
+        let usage = unsafe {
+            libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr());
+            usage.assume_init()
+        };
+        Usage {
+            user: duration(usage.ru_utime),
+            sys: duration(usage.ru_stime),
+            max_rss_kb: usage.ru_maxrss as i64,
+            minor_faults: usage.ru_minflt as i64,
+            major_faults: usage.ru_majflt as i64,
+            voluntary_switches: usage.ru_nvcsw as i64,
+            involuntary_switches: usage.ru_nivcsw as i64,
+        }
+    }
+    pub fn since(&self, start: &Usage) -> Usage {
+        Usage {
+            user: self.user.saturating_sub(start.user),
+            sys: self.sys.saturating_sub(start.sys),
+            max_rss_kb: self.max_rss_kb - start.max_rss_kb,
+            minor_faults: self.minor_faults - start.minor_faults,
+            major_faults: self.major_faults - start.major_faults,
+            voluntary_switches: self.voluntary_switches - start.voluntary_switches,
+            involuntary_switches: self.involuntary_switches - start.involuntary_switches,
+        }
+    }
+}